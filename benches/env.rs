@@ -0,0 +1,70 @@
+//! Applying a substitution to a large context, counting the allocations it takes.
+//! Run with `cargo bench --bench env`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use typing_test::env::{apply_subs_to_ctx, Context, Env};
+use typing_test::types::{Type, TypeScheme};
+use typing_test::unify::{appl_subs_to_scheme, unify, Substitution};
+
+/// The system allocator, but it counts how often it is asked for memory
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const BINDINGS: usize = 10_000;
+
+/// A context with a binding x{i} : t{i} -> t{i} for every i, and a substitution of all the t{i}
+fn setup() -> (Context, Substitution) {
+    let var = |i: usize| Box::new(Type::TVar { name: format!("t{}", i) });
+    let mut ctx = Context::new(Env::intial());
+    for i in 0..BINDINGS {
+        let type_ = Box::new(Type::TFun { from: var(i), to: var(i) });
+        ctx.env.0.insert(format!("x{}", i), TypeScheme::mono(type_));
+    }
+    let vars = Box::new(Type::TTuple { elements: (0..BINDINGS).map(var).collect() });
+    let ints = Box::new(Type::TTuple {
+        elements: (0..BINDINGS).map(|_| Box::new(Type::TNamed { name: "Int".to_string() })).collect(),
+    });
+    (ctx, unify(&vars, &ints).unwrap())
+}
+
+/// How apply_subs_to_ctx used to work, clone the whole context and then overwrite every binding
+fn clone_then_overwrite(subs: &Substitution, ctx: &Context) -> Context {
+    let mut new_ctx = ctx.clone();
+    for (name, scheme) in ctx.env.0.iter() {
+        new_ctx.env.0.insert(name.clone(), appl_subs_to_scheme(subs, scheme));
+    }
+    new_ctx
+}
+
+fn measure(name: &str, apply: impl Fn(&Substitution, &Context) -> Context) {
+    let (ctx, subs) = setup();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let applied = apply(&subs, &ctx);
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(applied.env.0.len(), ctx.env.0.len());
+    println!("{:<20} {:>8} allocations {:?}", name, allocations, elapsed);
+}
+
+fn main() {
+    measure("clone then overwrite", clone_then_overwrite);
+    measure("apply_subs_to_ctx", apply_subs_to_ctx);
+}
@@ -0,0 +1,55 @@
+//! Unification of wide tuple and record types, to catch accidental quadratic behaviour
+//! in the element and field matching. Run with `cargo bench --bench unify`
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use typing_test::types::Type;
+use typing_test::unify::unify;
+
+const WIDTHS: [usize; 3] = [1000, 2000, 4000];
+
+fn tvar(name: String) -> Box<Type> {
+    Box::new(Type::TVar { name })
+}
+
+fn int() -> Box<Type> {
+    Box::new(Type::TNamed { name: "Int".to_string() })
+}
+
+/// A tuple of distinct type variables and a tuple of as many integers
+fn tuples(width: usize) -> (Box<Type>, Box<Type>) {
+    let vars = (0..width).map(|i| tvar(format!("t{}", i))).collect();
+    let ints = (0..width).map(|_| int()).collect();
+    (Box::new(Type::TTuple { elements: vars }), Box::new(Type::TTuple { elements: ints }))
+}
+
+/// A record whose fields have distinct type variables and a record with the same fields of integers
+fn records(width: usize) -> (Box<Type>, Box<Type>) {
+    let field = |i: usize| format!("f{}", i);
+    let vars: BTreeMap<String, Box<Type>> = (0..width).map(|i| (field(i), tvar(format!("t{}", i)))).collect();
+    let ints: BTreeMap<String, Box<Type>> = (0..width).map(|i| (field(i), int())).collect();
+    (
+        Box::new(Type::TRecord { fields: vars, rest: None }),
+        Box::new(Type::TRecord { fields: ints, rest: None }),
+    )
+}
+
+/// The fastest of a few runs, which is the least disturbed by the rest of the machine
+fn time((t1, t2): &(Box<Type>, Box<Type>)) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            unify(t1, t2).unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    for (kind, make) in [("tuple", tuples as fn(usize) -> _), ("record", records)].iter() {
+        for width in WIDTHS.iter() {
+            println!("{:<6} of {:>4}: {:?}", kind, width, time(&make(*width)));
+        }
+    }
+}
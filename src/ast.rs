@@ -0,0 +1,1119 @@
+//! The expressions of the language and analyses on them that do not need inference
+
+use crate::env::Env;
+use crate::types::{alpha_equivalent, display_truncated, Type, TypeScheme};
+use std::collections::HashSet;
+use std::fmt;
+
+/// This is the expression that needs to be inferred, so the incoming expression as in the
+/// AST
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    EInt {
+        value: i32,
+    },
+    EBool {
+        value: bool,
+    },
+    // A string literal like `"abc"`
+    EString {
+        value: String,
+    },
+    // A character literal like `'a'`
+    EChar {
+        value: char,
+    },
+    EVar {
+        name: String,
+    },
+    EFunc {
+        param: String,
+        body: Box<Expression>,
+    },
+    // A function of several parameters like `\x y -> body`, this is sugar for nested
+    // single parameter functions and is desugared before inference
+    EFuncN {
+        params: Vec<String>,
+        body: Box<Expression>,
+    },
+    ECall {
+        func: Box<Expression>,
+        arg: Box<Expression>,
+    },
+    EIf {
+        cond: Box<Expression>,
+        true_b: Box<Expression>,
+        false_b: Box<Expression>,
+    },
+    // A tuple of expressions like `(1, true)`, `()` is the unit value
+    ETuple {
+        elements: Vec<Box<Expression>>,
+    },
+    // A list literal like `[1, 2, 3]`, all elements have the same type
+    EList {
+        elements: Vec<Box<Expression>>,
+    },
+    // A record literal like `{ name = 1, age = 2 }`, the fields are kept in source order
+    ERecord {
+        fields: Vec<(String, Box<Expression>)>,
+    },
+    // Access of a field of a record like `r.name`
+    EFieldAccess {
+        record: Box<Expression>,
+        field: String,
+    },
+    // Projection of an element of a tuple by its position like `p.0`
+    ETupleProject {
+        tuple: Box<Expression>,
+        index: usize,
+    },
+    // A constructor of a data type applied to its values like `Just 1`,
+    // with fewer values than the constructor holds it is a function of the missing ones
+    EConstructor {
+        name: String,
+        args: Vec<Box<Expression>>,
+    },
+    // Pick the first arm whose pattern fits the scrutinee: `match e with | p -> body | ...`
+    EMatch {
+        scrutinee: Box<Expression>,
+        arms: Vec<(Pattern, Box<Expression>)>,
+    },
+    // A binary operator applied to two operands like `1 + 2`
+    EBinOp {
+        op: BinOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    // An expression with the type it should have like `(\x -> x) : Int -> Int`
+    EAnnot {
+        expr: Box<Expression>,
+        type_: Box<Type>,
+    },
+    // A polymorphic expression used at the given type for its first quantified variable like `id @Int`
+    ETypeApp {
+        expr: Box<Expression>,
+        ty: Box<Type>,
+    },
+    // A contract check: `assert cond in body` has the type of the body
+    EAssert {
+        cond: Box<Expression>,
+        body: Box<Expression>,
+    },
+    // A local binding: `let name = value in body`
+    ELet {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    // A recursive local binding, the name can also be used inside of the value
+    ELetRec {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    // Mutually recursive local bindings: `let rec f = ... and g = ... in body`,
+    // every name can be used inside of every value
+    ELetRecGroup {
+        bindings: Vec<(String, Box<Expression>)>,
+        body: Box<Expression>,
+    },
+    // A local binding that takes the value apart: `let (a, b) = value in body`
+    ELetPattern {
+        pattern: Pattern,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    // Recover from a failed computation: `try expr catch error -> handler`,
+    // where the handler gets the error of the Result and produces its value instead
+    ETry {
+        expr: Box<Expression>,
+        error: String,
+        handler: Box<Expression>,
+    },
+    // A value from outside of the language, like a foreign function, that has the type it is given
+    // without anything to infer
+    EExtern {
+        ty: TypeScheme,
+    },
+    // An expression with the `-- comments` in front of it, only made when the parser keeps comments
+    EComment {
+        comments: Vec<String>,
+        expr: Box<Expression>,
+    },
+}
+
+/// The built in binary operators
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Lt,
+    Gt,
+    And,
+    Or,
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Eq => "==",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// The shape of a value in an arm of a match, the variables in it are bound in the arm
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    // Matches anything and binds it to the name, `_` is used for values that are not needed
+    PVar {
+        name: String,
+    },
+    PInt {
+        value: i32,
+    },
+    PBool {
+        value: bool,
+    },
+    PTuple {
+        elements: Vec<Pattern>,
+    },
+    // A constructor with patterns for all of its values like `Just x`
+    PConstructor {
+        name: String,
+        args: Vec<Pattern>,
+    },
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::PVar { name } => write!(f, "{}", name),
+            Pattern::PInt { value } => write!(f, "{}", value),
+            Pattern::PBool { value } => write!(f, "{}", value),
+            Pattern::PTuple { elements } => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+            Pattern::PConstructor { name, args } => {
+                write!(f, "{}", name)?;
+                for arg in args {
+                    // Arguments with arguments of their own need parentheses
+                    match arg {
+                        Pattern::PConstructor { args, .. } if !args.is_empty() => write!(f, " ({})", arg)?,
+                        _ => write!(f, " {}", arg)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Print the expression as source that parses back to the same expression,
+/// with only the parentheses that are needed
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_expr(self, 0, f)
+    }
+}
+
+/// How tightly the expression binds, following the grammar of the parser from `expr` at 0
+/// to `atom` at 10. An expression that binds looser than its place allows gets parentheses
+pub(crate) fn precedence(e: &Expression) -> u8 {
+    match e {
+        Expression::EInt { .. }
+        | Expression::EBool { .. }
+        | Expression::EString { .. }
+        | Expression::EChar { .. }
+        | Expression::EVar { .. }
+        | Expression::ETuple { .. }
+        | Expression::EList { .. }
+        | Expression::ERecord { .. }
+        | Expression::EExtern { .. } => 10,
+        Expression::EConstructor { args, .. } if args.is_empty() => 10,
+        Expression::EFieldAccess { .. } | Expression::ETupleProject { .. } => 9,
+        Expression::ECall { .. } | Expression::EConstructor { .. } | Expression::ETypeApp { .. } => 8,
+        Expression::EBinOp { op, .. } => op.precedence(),
+        Expression::EAnnot { .. } => 2,
+        Expression::EComment { expr, .. } => precedence(expr),
+        Expression::EFunc { .. }
+        | Expression::EFuncN { .. }
+        | Expression::EIf { .. }
+        | Expression::EMatch { .. }
+        | Expression::EAssert { .. }
+        | Expression::ELet { .. }
+        | Expression::ELetRec { .. }
+        | Expression::ELetRecGroup { .. }
+        | Expression::ELetPattern { .. }
+        | Expression::ETry { .. } => 0,
+    }
+}
+
+impl BinOp {
+    /// The operators chain to the left, except for the comparisons which do not chain at all
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 3,
+            BinOp::And => 4,
+            BinOp::Eq | BinOp::Lt | BinOp::Gt => 5,
+            BinOp::Add | BinOp::Sub => 6,
+            BinOp::Mul | BinOp::Div => 7,
+        }
+    }
+}
+
+/// Write the expression in a place that needs at least the given precedence
+fn write_expr(e: &Expression, min: u8, f: &mut fmt::Formatter) -> fmt::Result {
+    if precedence(e) < min {
+        write!(f, "(")?;
+        write_expr(e, 0, f)?;
+        return write!(f, ")");
+    }
+    match e {
+        Expression::EInt { value } => write!(f, "{}", value),
+        Expression::EBool { value } => write!(f, "{}", value),
+        Expression::EString { value } => write!(f, "\"{}\"", escape(value, '"')),
+        Expression::EChar { value } => write!(f, "'{}'", escape(&value.to_string(), '\'')),
+        Expression::EVar { name } => write!(f, "{}", name),
+        Expression::EFunc { param, body } => {
+            write!(f, "fun {} -> ", param)?;
+            write_expr(body, 0, f)
+        }
+        Expression::EFuncN { params, body } => {
+            write!(f, "fun {} -> ", params.join(" "))?;
+            write_expr(body, 0, f)
+        }
+        Expression::ECall { func, arg } => {
+            write_expr(func, 8, f)?;
+            write!(f, " ")?;
+            write_expr(arg, 9, f)
+        }
+        Expression::ETuple { elements } => {
+            write!(f, "(")?;
+            write_separated(elements, f)?;
+            write!(f, ")")
+        }
+        Expression::EList { elements } => {
+            write!(f, "[")?;
+            write_separated(elements, f)?;
+            write!(f, "]")
+        }
+        Expression::ERecord { fields } => {
+            if fields.is_empty() {
+                return write!(f, "{{}}");
+            }
+            write!(f, "{{ ")?;
+            for (i, (field, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{} = ", field)?;
+                write_expr(value, 0, f)?;
+            }
+            write!(f, " }}")
+        }
+        Expression::EFieldAccess { record, field } => {
+            write_expr(record, 9, f)?;
+            write!(f, ".{}", field)
+        }
+        Expression::ETupleProject { tuple, index } => {
+            write_expr(tuple, 9, f)?;
+            write!(f, ".{}", index)
+        }
+        Expression::EConstructor { name, args } => {
+            write!(f, "{}", name)?;
+            for arg in args {
+                write!(f, " ")?;
+                write_expr(arg, 9, f)?;
+            }
+            Ok(())
+        }
+        Expression::EMatch { scrutinee, arms } => {
+            write!(f, "match ")?;
+            write_expr(scrutinee, 0, f)?;
+            write!(f, " with")?;
+            for (i, (pattern, body)) in arms.iter().enumerate() {
+                write!(f, " | {} -> ", pattern)?;
+                // A match in an arm that is not the last would take the arms that follow
+                write_expr(body, if i + 1 < arms.len() { 1 } else { 0 }, f)?;
+            }
+            Ok(())
+        }
+        Expression::EIf { cond, true_b, false_b } => {
+            write!(f, "if ")?;
+            write_expr(cond, 0, f)?;
+            write!(f, " then ")?;
+            write_expr(true_b, 0, f)?;
+            write!(f, " else ")?;
+            write_expr(false_b, 0, f)
+        }
+        Expression::EBinOp { op, lhs, rhs } => {
+            let precedence = op.precedence();
+            let chains = !matches!(op, BinOp::Eq | BinOp::Lt | BinOp::Gt);
+            write_expr(lhs, if chains { precedence } else { precedence + 1 }, f)?;
+            write!(f, " {} ", op)?;
+            write_expr(rhs, precedence + 1, f)
+        }
+        Expression::EAnnot { expr, type_ } => {
+            write_expr(expr, 3, f)?;
+            write!(f, " : {}", display_truncated(type_, usize::MAX))
+        }
+        Expression::ETypeApp { expr, ty } => {
+            write_expr(expr, 8, f)?;
+            match ty.as_ref() {
+                Type::TNamed { .. } | Type::TVar { .. } | Type::TTuple { .. } => {
+                    write!(f, " @{}", display_truncated(ty, usize::MAX))
+                }
+                _ => write!(f, " @({})", display_truncated(ty, usize::MAX)),
+            }
+        }
+        Expression::EAssert { cond, body } => {
+            write!(f, "assert ")?;
+            write_expr(cond, 0, f)?;
+            write!(f, " in ")?;
+            write_expr(body, 0, f)
+        }
+        Expression::ELet { name, value, body } | Expression::ELetRec { name, value, body } => {
+            let rec = if matches!(e, Expression::ELetRec { .. }) { "rec " } else { "" };
+            write!(f, "let {}{} = ", rec, name)?;
+            write_expr(value, 0, f)?;
+            write!(f, " in ")?;
+            write_expr(body, 0, f)
+        }
+        Expression::ELetRecGroup { bindings, body } => {
+            for (i, (name, value)) in bindings.iter().enumerate() {
+                write!(f, "{} {} = ", if i == 0 { "let rec" } else { " and" }, name)?;
+                write_expr(value, 0, f)?;
+            }
+            write!(f, " in ")?;
+            write_expr(body, 0, f)
+        }
+        Expression::ELetPattern { pattern, value, body } => {
+            // The parser knows this let by the parenthesis that follows it
+            match pattern {
+                Pattern::PTuple { .. } => write!(f, "let {} = ", pattern)?,
+                _ => write!(f, "let ({}) = ", pattern)?,
+            }
+            write_expr(value, 0, f)?;
+            write!(f, " in ")?;
+            write_expr(body, 0, f)
+        }
+        Expression::ETry { expr, error, handler } => {
+            write!(f, "try ")?;
+            write_expr(expr, 0, f)?;
+            write!(f, " catch {} -> ", error)?;
+            write_expr(handler, 0, f)
+        }
+        // There is no source for a value from outside of the language, only its type
+        Expression::EExtern { ty } => write!(f, "<extern : {}>", display_truncated(&ty.type_, usize::MAX)),
+        Expression::EComment { comments, expr } => {
+            for comment in comments {
+                writeln!(f, "-- {}", comment)?;
+            }
+            write_expr(expr, min, f)
+        }
+    }
+}
+
+/// Escape the characters that the lexer reads differently in a literal with the given quotes
+fn escape(text: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\\' => escaped.push_str("\\\\"),
+            _ if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_separated(elements: &[Box<Expression>], f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write_expr(element, 0, f)?;
+    }
+    Ok(())
+}
+
+/// Collect the variables that a pattern binds, from left to right
+pub fn pattern_vars(p: &Pattern) -> Vec<String> {
+    fn go(p: &Pattern, found: &mut Vec<String>) {
+        match p {
+            Pattern::PVar { name } => found.push(name.clone()),
+            Pattern::PInt { .. } | Pattern::PBool { .. } => {}
+            Pattern::PTuple { elements: patterns } | Pattern::PConstructor { args: patterns, .. } => {
+                for pattern in patterns {
+                    go(pattern, found);
+                }
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    go(p, &mut found);
+    found
+}
+
+/// Collect the variables that are used but not bound inside the expression,
+/// in the order in which they first occur
+pub fn free_vars(e: &Expression) -> Vec<String> {
+    fn go(e: &Expression, bound: &mut Vec<String>, found: &mut Vec<String>) {
+        match e {
+            Expression::EInt { .. }
+            | Expression::EBool { .. }
+            | Expression::EString { .. }
+            | Expression::EChar { .. }
+            | Expression::EExtern { .. } => {}
+            Expression::EVar { name } => {
+                if !bound.contains(name) && !found.contains(name) {
+                    found.push(name.clone());
+                }
+            }
+            Expression::EFunc { param, body } => {
+                // The parameter is only bound inside of the body
+                bound.push(param.clone());
+                go(body, bound, found);
+                bound.pop();
+            }
+            Expression::EFuncN { params, body } => {
+                bound.extend(params.iter().cloned());
+                go(body, bound, found);
+                bound.truncate(bound.len() - params.len());
+            }
+            Expression::ECall { func, arg } => {
+                go(func, bound, found);
+                go(arg, bound, found);
+            }
+            Expression::ETuple { elements } | Expression::EList { elements } | Expression::EConstructor { args: elements, .. } => {
+                for element in elements {
+                    go(element, bound, found);
+                }
+            }
+            Expression::ERecord { fields } => {
+                for (_, value) in fields {
+                    go(value, bound, found);
+                }
+            }
+            Expression::EFieldAccess { record, .. }
+            | Expression::ETupleProject { tuple: record, .. }
+            | Expression::EAnnot { expr: record, .. }
+            | Expression::ETypeApp { expr: record, .. }
+            | Expression::EComment { expr: record, .. } => go(record, bound, found),
+            Expression::EMatch { scrutinee, arms } => {
+                go(scrutinee, bound, found);
+                // The variables of a pattern are only bound inside of its arm
+                for (pattern, body) in arms {
+                    let vars = pattern_vars(pattern);
+                    let count = vars.len();
+                    bound.extend(vars);
+                    go(body, bound, found);
+                    bound.truncate(bound.len() - count);
+                }
+            }
+            Expression::EIf { cond, true_b, false_b } => {
+                go(cond, bound, found);
+                go(true_b, bound, found);
+                go(false_b, bound, found);
+            }
+            Expression::EBinOp { lhs, rhs, .. } => {
+                go(lhs, bound, found);
+                go(rhs, bound, found);
+            }
+            Expression::EAssert { cond, body } => {
+                go(cond, bound, found);
+                go(body, bound, found);
+            }
+            Expression::ELet { name, value, body } => {
+                // The name is only bound inside of the body
+                go(value, bound, found);
+                bound.push(name.clone());
+                go(body, bound, found);
+                bound.pop();
+            }
+            Expression::ELetRec { name, value, body } => {
+                // The name is bound inside of the value as well
+                bound.push(name.clone());
+                go(value, bound, found);
+                go(body, bound, found);
+                bound.pop();
+            }
+            Expression::ELetRecGroup { bindings, body } => {
+                // Every name is bound inside of every value
+                let before = bound.len();
+                bound.extend(bindings.iter().map(|(name, _)| name.clone()));
+                for (_, value) in bindings {
+                    go(value, bound, found);
+                }
+                go(body, bound, found);
+                bound.truncate(before);
+            }
+            Expression::ELetPattern { pattern, value, body } => {
+                // Like a let, the variables of the pattern are only bound inside of the body
+                go(value, bound, found);
+                let vars = pattern_vars(pattern);
+                let count = vars.len();
+                bound.extend(vars);
+                go(body, bound, found);
+                bound.truncate(bound.len() - count);
+            }
+            Expression::ETry { expr, error, handler } => {
+                // The error is only bound inside of the handler
+                go(expr, bound, found);
+                bound.push(error.clone());
+                go(handler, bound, found);
+                bound.pop();
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    go(e, &mut Vec::new(), &mut found);
+    found
+}
+
+/// Return the builtins that the expression actually references,
+/// e.g. `not x` only depends on `not` and `1 + 2` only on `+`.
+/// Names count when the environment binds them, operators are reported by their symbol
+pub fn used_builtins(e: &Expression, env: &Env) -> HashSet<String> {
+    fn go(e: &Expression, found: &mut HashSet<String>) {
+        match e {
+            Expression::EInt { .. }
+            | Expression::EBool { .. }
+            | Expression::EString { .. }
+            | Expression::EChar { .. }
+            | Expression::EVar { .. }
+            | Expression::EExtern { .. } => {}
+            Expression::EFunc { body, .. } | Expression::EFuncN { body, .. } => go(body, found),
+            Expression::ECall { func, arg } => {
+                go(func, found);
+                go(arg, found);
+            }
+            Expression::ETuple { elements } | Expression::EList { elements } | Expression::EConstructor { args: elements, .. } => {
+                for element in elements {
+                    go(element, found);
+                }
+            }
+            Expression::ERecord { fields } => {
+                for (_, value) in fields {
+                    go(value, found);
+                }
+            }
+            Expression::EFieldAccess { record, .. }
+            | Expression::ETupleProject { tuple: record, .. }
+            | Expression::EAnnot { expr: record, .. }
+            | Expression::ETypeApp { expr: record, .. }
+            | Expression::EComment { expr: record, .. } => go(record, found),
+            Expression::EMatch { scrutinee, arms } => {
+                go(scrutinee, found);
+                for (_, body) in arms {
+                    go(body, found);
+                }
+            }
+            Expression::EIf { cond, true_b, false_b } => {
+                go(cond, found);
+                go(true_b, found);
+                go(false_b, found);
+            }
+            Expression::EBinOp { op, lhs, rhs } => {
+                // Operators can't be shadowed, so they always refer to the builtin
+                found.insert(op.to_string());
+                go(lhs, found);
+                go(rhs, found);
+            }
+            Expression::EAssert { cond, body } => {
+                go(cond, found);
+                go(body, found);
+            }
+            Expression::ELet { value, body, .. }
+            | Expression::ELetRec { value, body, .. }
+            | Expression::ELetPattern { value, body, .. }
+            | Expression::ETry { expr: value, handler: body, .. } => {
+                go(value, found);
+                go(body, found);
+            }
+            Expression::ELetRecGroup { bindings, body } => {
+                for (_, value) in bindings {
+                    go(value, found);
+                }
+                go(body, found);
+            }
+        }
+    }
+
+    let mut found: HashSet<String> = free_vars(e)
+        .into_iter()
+        .filter(|name| env.0.contains_key(name))
+        .collect();
+    go(e, &mut found);
+    found
+}
+
+/// Check if two expressions are the same up to the names of their bound variables,
+/// eg. `fun x -> x` and `fun y -> y` are
+pub fn alpha_equivalent_exprs(e1: &Expression, e2: &Expression) -> bool {
+    // Pairs of parameter names that are bound at the same place, innermost last
+    fn go(e1: &Expression, e2: &Expression, bound: &mut Vec<(String, String)>) -> bool {
+        match (e1, e2) {
+            // Comments don't change what an expression means
+            (Expression::EComment { expr, .. }, _) => go(expr, e2, bound),
+            (_, Expression::EComment { expr, .. }) => go(e1, expr, bound),
+            (Expression::EInt { value }, Expression::EInt { value: value2 }) => value == value2,
+            (Expression::EBool { value }, Expression::EBool { value: value2 }) => value == value2,
+            (Expression::EString { value }, Expression::EString { value: value2 }) => value == value2,
+            (Expression::EChar { value }, Expression::EChar { value: value2 }) => value == value2,
+            (Expression::EVar { name }, Expression::EVar { name: name2 }) => {
+                match bound.iter().rev().find(|(l, r)| l == name || r == name2) {
+                    Some((l, r)) => l == name && r == name2,
+                    // Free variables have to be the same
+                    None => name == name2,
+                }
+            }
+            (Expression::EFunc { param, body }, Expression::EFunc { param: param2, body: body2 }) => {
+                bound.push((param.clone(), param2.clone()));
+                let same = go(body, body2, bound);
+                bound.pop();
+                same
+            }
+            (Expression::EFuncN { params, body }, Expression::EFuncN { params: params2, body: body2 }) => {
+                if params.len() != params2.len() {
+                    return false;
+                }
+                bound.extend(params.iter().cloned().zip(params2.iter().cloned()));
+                let same = go(body, body2, bound);
+                bound.truncate(bound.len() - params.len());
+                same
+            }
+            (Expression::ECall { func, arg }, Expression::ECall { func: func2, arg: arg2 }) => {
+                go(func, func2, bound) && go(arg, arg2, bound)
+            }
+            (Expression::EIf { cond, true_b, false_b },
+             Expression::EIf { cond: cond2, true_b: true_b2, false_b: false_b2 }) => {
+                go(cond, cond2, bound) && go(true_b, true_b2, bound) && go(false_b, false_b2, bound)
+            }
+            (Expression::ETuple { elements }, Expression::ETuple { elements: elements2 })
+            | (Expression::EList { elements }, Expression::EList { elements: elements2 }) => {
+                elements.len() == elements2.len()
+                    && elements.iter().zip(elements2).all(|(l, r)| go(l, r, bound))
+            }
+            (Expression::EConstructor { name, args }, Expression::EConstructor { name: name2, args: args2 }) => {
+                name == name2 && args.len() == args2.len() && args.iter().zip(args2).all(|(l, r)| go(l, r, bound))
+            }
+            (Expression::ERecord { fields }, Expression::ERecord { fields: fields2 }) => {
+                fields.len() == fields2.len()
+                    && fields
+                        .iter()
+                        .zip(fields2)
+                        .all(|((field, l), (field2, r))| field == field2 && go(l, r, bound))
+            }
+            (Expression::EFieldAccess { record, field }, Expression::EFieldAccess { record: record2, field: field2 }) => {
+                field == field2 && go(record, record2, bound)
+            }
+            (Expression::ETupleProject { tuple, index }, Expression::ETupleProject { tuple: tuple2, index: index2 }) => {
+                index == index2 && go(tuple, tuple2, bound)
+            }
+            (Expression::EMatch { scrutinee, arms }, Expression::EMatch { scrutinee: scrutinee2, arms: arms2 }) => {
+                if !go(scrutinee, scrutinee2, bound) || arms.len() != arms2.len() {
+                    return false;
+                }
+                arms.iter().zip(arms2).all(|((pattern, body), (pattern2, body2))| {
+                    let before = bound.len();
+                    let same = same_pattern(pattern, pattern2, bound) && go(body, body2, bound);
+                    bound.truncate(before);
+                    same
+                })
+            }
+            (Expression::EBinOp { op, lhs, rhs }, Expression::EBinOp { op: op2, lhs: lhs2, rhs: rhs2 }) => {
+                op == op2 && go(lhs, lhs2, bound) && go(rhs, rhs2, bound)
+            }
+            (Expression::EAnnot { expr, type_ }, Expression::EAnnot { expr: expr2, type_: type2 })
+            | (Expression::ETypeApp { expr, ty: type_ }, Expression::ETypeApp { expr: expr2, ty: type2 }) => {
+                type_ == type2 && go(expr, expr2, bound)
+            }
+            (Expression::EAssert { cond, body }, Expression::EAssert { cond: cond2, body: body2 }) => {
+                go(cond, cond2, bound) && go(body, body2, bound)
+            }
+            (Expression::ETry { expr, error, handler },
+             Expression::ETry { expr: expr2, error: error2, handler: handler2 }) => {
+                if !go(expr, expr2, bound) {
+                    return false;
+                }
+                bound.push((error.clone(), error2.clone()));
+                let same = go(handler, handler2, bound);
+                bound.pop();
+                same
+            }
+            (Expression::ELet { name, value, body },
+             Expression::ELet { name: name2, value: value2, body: body2 }) => {
+                if !go(value, value2, bound) {
+                    return false;
+                }
+                bound.push((name.clone(), name2.clone()));
+                let same = go(body, body2, bound);
+                bound.pop();
+                same
+            }
+            (Expression::ELetRec { name, value, body },
+             Expression::ELetRec { name: name2, value: value2, body: body2 }) => {
+                bound.push((name.clone(), name2.clone()));
+                let same = go(value, value2, bound) && go(body, body2, bound);
+                bound.pop();
+                same
+            }
+            (Expression::ELetRecGroup { bindings, body },
+             Expression::ELetRecGroup { bindings: bindings2, body: body2 }) => {
+                if bindings.len() != bindings2.len() {
+                    return false;
+                }
+                let before = bound.len();
+                let names = bindings.iter().zip(bindings2).map(|((name, _), (name2, _))| (name.clone(), name2.clone()));
+                bound.extend(names);
+                let same = bindings.iter().zip(bindings2).all(|((_, value), (_, value2))| go(value, value2, bound))
+                    && go(body, body2, bound);
+                bound.truncate(before);
+                same
+            }
+            (Expression::ELetPattern { pattern, value, body },
+             Expression::ELetPattern { pattern: pattern2, value: value2, body: body2 }) => {
+                if !go(value, value2, bound) {
+                    return false;
+                }
+                let before = bound.len();
+                let same = same_pattern(pattern, pattern2, bound) && go(body, body2, bound);
+                bound.truncate(before);
+                same
+            }
+            (Expression::EExtern { ty }, Expression::EExtern { ty: ty2 }) => {
+                ty.vars.len() == ty2.vars.len() && alpha_equivalent(&ty.type_, &ty2.type_)
+            }
+            (_, _) => false,
+        }
+    }
+
+    // Patterns have to have the same shape, their variables are bound at the same place
+    fn same_pattern(p1: &Pattern, p2: &Pattern, bound: &mut Vec<(String, String)>) -> bool {
+        match (p1, p2) {
+            (Pattern::PVar { name }, Pattern::PVar { name: name2 }) => {
+                bound.push((name.clone(), name2.clone()));
+                true
+            }
+            (Pattern::PInt { value }, Pattern::PInt { value: value2 }) => value == value2,
+            (Pattern::PBool { value }, Pattern::PBool { value: value2 }) => value == value2,
+            (Pattern::PTuple { elements }, Pattern::PTuple { elements: elements2 }) => {
+                elements.len() == elements2.len()
+                    && elements.iter().zip(elements2).all(|(l, r)| same_pattern(l, r, bound))
+            }
+            (Pattern::PConstructor { name, args }, Pattern::PConstructor { name: name2, args: args2 }) => {
+                name == name2
+                    && args.len() == args2.len()
+                    && args.iter().zip(args2).all(|(l, r)| same_pattern(l, r, bound))
+            }
+            (_, _) => false,
+        }
+    }
+
+    go(e1, e2, &mut Vec::new())
+}
+
+/// Warnings do not stop the inference, but point at suspicious code
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    // A binding hides a name of the initial environment like `not`
+    ShadowsBuiltin(String),
+    // Both branches of this if are the same, so the condition does not matter
+    RedundantIf(Expression),
+    // No arm of a match fits values like the given pattern
+    NonExhaustiveMatch(Pattern),
+    // Earlier arms already cover every value this pattern fits, so its arm is never used
+    UnreachableArm(Pattern),
+}
+
+/// Report every binding in the expression that shadows a name from the environment
+pub fn shadowed_builtins(e: &Expression, env: &Env) -> Vec<Warning> {
+    fn go(e: &Expression, env: &Env, warnings: &mut Vec<Warning>) {
+        match e {
+            Expression::EInt { .. }
+            | Expression::EBool { .. }
+            | Expression::EString { .. }
+            | Expression::EChar { .. }
+            | Expression::EVar { .. }
+            | Expression::EExtern { .. } => {}
+            Expression::EFunc { param, body } => {
+                if env.0.contains_key(param) {
+                    warnings.push(Warning::ShadowsBuiltin(param.clone()));
+                }
+                go(body, env, warnings);
+            }
+            Expression::EFuncN { params, body } => {
+                for param in params.iter().filter(|param| env.0.contains_key(*param)) {
+                    warnings.push(Warning::ShadowsBuiltin(param.clone()));
+                }
+                go(body, env, warnings);
+            }
+            Expression::ECall { func, arg } => {
+                go(func, env, warnings);
+                go(arg, env, warnings);
+            }
+            Expression::ETuple { elements } | Expression::EList { elements } | Expression::EConstructor { args: elements, .. } => {
+                for element in elements {
+                    go(element, env, warnings);
+                }
+            }
+            Expression::ERecord { fields } => {
+                for (_, value) in fields {
+                    go(value, env, warnings);
+                }
+            }
+            Expression::EFieldAccess { record, .. }
+            | Expression::ETupleProject { tuple: record, .. }
+            | Expression::EAnnot { expr: record, .. }
+            | Expression::ETypeApp { expr: record, .. }
+            | Expression::EComment { expr: record, .. } => go(record, env, warnings),
+            Expression::EMatch { scrutinee, arms } => {
+                go(scrutinee, env, warnings);
+                for (pattern, body) in arms {
+                    for name in pattern_vars(pattern) {
+                        if env.0.contains_key(&name) {
+                            warnings.push(Warning::ShadowsBuiltin(name));
+                        }
+                    }
+                    go(body, env, warnings);
+                }
+            }
+            Expression::EIf { cond, true_b, false_b } => {
+                go(cond, env, warnings);
+                go(true_b, env, warnings);
+                go(false_b, env, warnings);
+            }
+            Expression::EBinOp { lhs, rhs, .. } => {
+                go(lhs, env, warnings);
+                go(rhs, env, warnings);
+            }
+            Expression::EAssert { cond, body } => {
+                go(cond, env, warnings);
+                go(body, env, warnings);
+            }
+            Expression::ELet { name, value, body } | Expression::ELetRec { name, value, body } => {
+                if env.0.contains_key(name) {
+                    warnings.push(Warning::ShadowsBuiltin(name.clone()));
+                }
+                go(value, env, warnings);
+                go(body, env, warnings);
+            }
+            Expression::ELetRecGroup { bindings, body } => {
+                for (name, value) in bindings {
+                    if env.0.contains_key(name) {
+                        warnings.push(Warning::ShadowsBuiltin(name.clone()));
+                    }
+                    go(value, env, warnings);
+                }
+                go(body, env, warnings);
+            }
+            Expression::ELetPattern { pattern, value, body } => {
+                for name in pattern_vars(pattern) {
+                    if env.0.contains_key(&name) {
+                        warnings.push(Warning::ShadowsBuiltin(name));
+                    }
+                }
+                go(value, env, warnings);
+                go(body, env, warnings);
+            }
+            Expression::ETry { expr, error, handler } => {
+                if env.0.contains_key(error) {
+                    warnings.push(Warning::ShadowsBuiltin(error.clone()));
+                }
+                go(expr, env, warnings);
+                go(handler, env, warnings);
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    go(e, env, &mut warnings);
+    warnings
+}
+
+/// Report every if expression of which both branches are the same expression
+pub fn redundant_ifs(e: &Expression) -> Vec<Warning> {
+    fn go(e: &Expression, warnings: &mut Vec<Warning>) {
+        match e {
+            Expression::EInt { .. }
+            | Expression::EBool { .. }
+            | Expression::EString { .. }
+            | Expression::EChar { .. }
+            | Expression::EVar { .. }
+            | Expression::EExtern { .. } => {}
+            Expression::EFunc { body, .. } | Expression::EFuncN { body, .. } => go(body, warnings),
+            Expression::ECall { func, arg } => {
+                go(func, warnings);
+                go(arg, warnings);
+            }
+            Expression::ETuple { elements } | Expression::EList { elements } | Expression::EConstructor { args: elements, .. } => {
+                for element in elements {
+                    go(element, warnings);
+                }
+            }
+            Expression::ERecord { fields } => {
+                for (_, value) in fields {
+                    go(value, warnings);
+                }
+            }
+            Expression::EFieldAccess { record, .. }
+            | Expression::ETupleProject { tuple: record, .. }
+            | Expression::EAnnot { expr: record, .. }
+            | Expression::ETypeApp { expr: record, .. }
+            | Expression::EComment { expr: record, .. } => go(record, warnings),
+            Expression::EMatch { scrutinee, arms } => {
+                go(scrutinee, warnings);
+                for (_, body) in arms {
+                    go(body, warnings);
+                }
+            }
+            Expression::EIf { cond, true_b, false_b } => {
+                if alpha_equivalent_exprs(true_b, false_b) {
+                    warnings.push(Warning::RedundantIf(e.clone()));
+                }
+                go(cond, warnings);
+                go(true_b, warnings);
+                go(false_b, warnings);
+            }
+            Expression::EBinOp { lhs, rhs, .. } => {
+                go(lhs, warnings);
+                go(rhs, warnings);
+            }
+            Expression::EAssert { cond, body } => {
+                go(cond, warnings);
+                go(body, warnings);
+            }
+            Expression::ELet { value, body, .. }
+            | Expression::ELetRec { value, body, .. }
+            | Expression::ELetPattern { value, body, .. }
+            | Expression::ETry { expr: value, handler: body, .. } => {
+                go(value, warnings);
+                go(body, warnings);
+            }
+            Expression::ELetRecGroup { bindings, body } => {
+                for (_, value) in bindings {
+                    go(value, warnings);
+                }
+                go(body, warnings);
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    go(e, &mut warnings);
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::test_util::{binop, bool, call, func, funcn, if_, int, let_, match_, pcon, ptuple, pvar, var};
+
+    #[test]
+    fn free_vars_are_in_order_of_first_use() {
+        let e = call(call(var("f"), var("x")), func("x", call(var("g"), var("f"))));
+        assert_eq!(free_vars(&e), vec!["f", "x", "g"]);
+    }
+
+    #[test]
+    fn used_builtins_only_reports_environment_names() {
+        let e = call(call(var("cons"), call(var("not"), bool(true))), var("y"));
+        let expected: HashSet<String> = ["cons", "not"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(used_builtins(&e, &Env::intial()), expected);
+    }
+
+    #[test]
+    fn used_builtins_reports_operators() {
+        let e = binop(BinOp::Add, int(1), int(2));
+        let expected: HashSet<String> = ["+"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(used_builtins(&e, &Env::intial()), expected);
+    }
+
+    #[test]
+    fn prints_expressions_back_as_source() {
+        for src in [
+            "(fun x -> x) (1 + 2) * 3",
+            "1 - (2 - 3) : Int -> List a",
+            "let rec f = fun x y -> f y x in f.a.0 [1, 2] { a = (), b = Just (Just 1) }",
+            "let rec f = fun n -> g n and g = fun n -> f n in f 1",
+            "match m with | Just (x, y) -> (match x with | 1 -> true | _ -> false) | Nothing -> a || b && c",
+            "let (Just x) = m in assert x == 1 in if x < 2 then x else 0",
+            "f @Int @(List a) (g @b x)",
+            r#"index "a \"quoted\"\n\\" 0 == '\''"#,
+        ]
+        .iter()
+        {
+            assert_eq!(parse(src).unwrap().to_string(), *src);
+        }
+    }
+
+    #[test]
+    fn warns_about_shadowed_builtins() {
+        let e = let_("not", int(1), var("not"));
+        assert_eq!(shadowed_builtins(&e, &Env::intial()), vec![Warning::ShadowsBuiltin("not".to_string())]);
+    }
+
+    #[test]
+    fn pattern_variables_are_bound_in_their_arm() {
+        let e = match_(var("m"), vec![
+            (pcon("Just", vec![ptuple(vec![pvar("x"), pvar("y")])]), call(var("x"), var("z"))),
+            (pvar("_"), var("x")),
+        ]);
+        assert_eq!(free_vars(&e), vec!["m", "z", "x"]);
+
+        let renamed = match_(var("m"), vec![
+            (pcon("Just", vec![ptuple(vec![pvar("a"), pvar("b")])]), call(var("a"), var("z"))),
+            (pvar("c"), var("x")),
+        ]);
+        assert!(alpha_equivalent_exprs(&e, &renamed));
+    }
+
+    #[test]
+    fn all_parameters_of_a_function_are_bound() {
+        let e = funcn(&["x", "y"], call(call(var("x"), var("y")), var("z")));
+        assert_eq!(free_vars(&e), vec!["z"]);
+        assert!(alpha_equivalent_exprs(&e, &funcn(&["a", "b"], call(call(var("a"), var("b")), var("z")))));
+        assert!(!alpha_equivalent_exprs(&e, &funcn(&["a", "b"], call(call(var("b"), var("a")), var("z")))));
+    }
+
+    #[test]
+    fn display_patterns() {
+        let p = pcon("Just", vec![pcon("Just", vec![pvar("x")]), pcon("Nothing", vec![]), ptuple(vec![])]);
+        assert_eq!(p.to_string(), "Just (Just x) Nothing ()");
+    }
+
+    #[test]
+    fn warns_about_if_with_identical_branches() {
+        let e = func("c", if_(var("c"), int(1), int(1)));
+        assert_eq!(redundant_ifs(&e).len(), 1);
+        assert!(redundant_ifs(&if_(var("c"), int(1), int(2))).is_empty());
+    }
+
+    #[test]
+    fn alpha_equivalent_exprs_ignores_bound_names() {
+        assert!(alpha_equivalent_exprs(&func("x", var("x")), &func("y", var("y"))));
+        assert!(!alpha_equivalent_exprs(&func("x", var("x")), &func("y", var("x"))));
+    }
+}
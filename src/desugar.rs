@@ -0,0 +1,156 @@
+//! Rewrites the syntactic sugar of the language into the core expressions that inference handles
+
+use crate::ast::Expression;
+
+/// Expand every function of several parameters into nested single parameter functions,
+/// eg. `\x y -> body` becomes `\x -> \y -> body`
+pub fn desugar(e: &Expression) -> Expression {
+    let boxed = |e: &Box<Expression>| Box::new(desugar(e));
+    let all = |es: &Vec<Box<Expression>>| es.iter().map(boxed).collect();
+    match e {
+        Expression::EInt { .. }
+        | Expression::EBool { .. }
+        | Expression::EString { .. }
+        | Expression::EChar { .. }
+        | Expression::EVar { .. }
+        | Expression::EExtern { .. } => e.clone(),
+        Expression::EFunc { param, body } => Expression::EFunc { param: param.clone(), body: boxed(body) },
+        Expression::EFuncN { params, body } => params
+            .iter()
+            .rev()
+            .fold(desugar(body), |body, param| Expression::EFunc { param: param.clone(), body: Box::new(body) }),
+        Expression::ECall { func, arg } => Expression::ECall { func: boxed(func), arg: boxed(arg) },
+        Expression::EIf { cond, true_b, false_b } => Expression::EIf {
+            cond: boxed(cond),
+            true_b: boxed(true_b),
+            false_b: boxed(false_b),
+        },
+        Expression::ETuple { elements } => Expression::ETuple { elements: all(elements) },
+        Expression::EList { elements } => Expression::EList { elements: all(elements) },
+        Expression::ERecord { fields } => Expression::ERecord {
+            fields: fields.iter().map(|(field, value)| (field.clone(), boxed(value))).collect(),
+        },
+        Expression::EFieldAccess { record, field } => Expression::EFieldAccess { record: boxed(record), field: field.clone() },
+        Expression::ETupleProject { tuple, index } => Expression::ETupleProject { tuple: boxed(tuple), index: *index },
+        Expression::EConstructor { name, args } => Expression::EConstructor { name: name.clone(), args: all(args) },
+        Expression::EMatch { scrutinee, arms } => Expression::EMatch {
+            scrutinee: boxed(scrutinee),
+            arms: arms.iter().map(|(pattern, body)| (pattern.clone(), boxed(body))).collect(),
+        },
+        Expression::EBinOp { op, lhs, rhs } => Expression::EBinOp { op: *op, lhs: boxed(lhs), rhs: boxed(rhs) },
+        Expression::EAnnot { expr, type_ } => Expression::EAnnot { expr: boxed(expr), type_: type_.clone() },
+        Expression::ETry { expr, error, handler } => Expression::ETry {
+            expr: boxed(expr),
+            error: error.clone(),
+            handler: boxed(handler),
+        },
+        Expression::EComment { comments, expr } => Expression::EComment { comments: comments.clone(), expr: boxed(expr) },
+        Expression::ETypeApp { expr, ty } => Expression::ETypeApp { expr: boxed(expr), ty: ty.clone() },
+        Expression::EAssert { cond, body } => Expression::EAssert { cond: boxed(cond), body: boxed(body) },
+        Expression::ELet { name, value, body } => Expression::ELet {
+            name: name.clone(),
+            value: boxed(value),
+            body: boxed(body),
+        },
+        Expression::ELetRec { name, value, body } => Expression::ELetRec {
+            name: name.clone(),
+            value: boxed(value),
+            body: boxed(body),
+        },
+        Expression::ELetRecGroup { bindings, body } => Expression::ELetRecGroup {
+            bindings: bindings.iter().map(|(name, value)| (name.clone(), boxed(value))).collect(),
+            body: boxed(body),
+        },
+        Expression::ELetPattern { pattern, value, body } => Expression::ELetPattern {
+            pattern: pattern.clone(),
+            value: boxed(value),
+            body: boxed(body),
+        },
+    }
+}
+
+/// A part of a list comprehension after the `|`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Qualifier {
+    // Take the variable from every element of the list: `x <- xs`
+    Generator {
+        var: String,
+        list: Box<Expression>,
+    },
+    // Only keep the elements for which the condition holds
+    Guard(Box<Expression>),
+}
+
+/// Turn a list comprehension into calls of map, filter and concatMap, the parser makes these
+/// so inference only sees the calls. The last generator maps, the ones before it concatMap,
+/// and the guards after a generator filter its list. So `[e | x <- xs, y <- ys, c]` becomes
+/// `concatMap (fun x -> map (fun y -> e) (filter (fun y -> c) ys)) xs`
+pub fn desugar_comprehension(element: Box<Expression>, qualifiers: &[Qualifier]) -> Expression {
+    let var = |name: &str| Box::new(Expression::EVar { name: name.to_string() });
+    let call2 = |name: &str, func: Box<Expression>, arg: Box<Expression>| Expression::ECall {
+        func: Box::new(Expression::ECall { func: var(name), arg: func }),
+        arg,
+    };
+    match qualifiers.split_first() {
+        None => Expression::EList { elements: vec![element] },
+        // A guard before any generator keeps all or nothing
+        Some((Qualifier::Guard(cond), rest)) => Expression::EIf {
+            cond: cond.clone(),
+            true_b: Box::new(desugar_comprehension(element, rest)),
+            false_b: Box::new(Expression::EList { elements: Vec::new() }),
+        },
+        Some((Qualifier::Generator { var: name, list }, rest)) => {
+            let guards = rest.iter().take_while(|qualifier| matches!(qualifier, Qualifier::Guard(_))).count();
+            let mut list = list.clone();
+            for qualifier in &rest[..guards] {
+                if let Qualifier::Guard(cond) = qualifier {
+                    let keep = Box::new(Expression::EFunc { param: name.clone(), body: cond.clone() });
+                    list = Box::new(call2("filter", keep, list));
+                }
+            }
+            let rest = &rest[guards..];
+            if rest.is_empty() {
+                call2("map", Box::new(Expression::EFunc { param: name.clone(), body: element }), list)
+            } else {
+                let body = Box::new(desugar_comprehension(element, rest));
+                call2("concatMap", Box::new(Expression::EFunc { param: name.clone(), body }), list)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinOp;
+    use crate::test_util::{binop, call, func, funcn, int, let_, var};
+
+    #[test]
+    fn expands_into_nested_functions() {
+        let e = funcn(&["f", "x", "y"], call(call(var("f"), var("x")), var("y")));
+        assert_eq!(desugar(&e), *func("f", func("x", func("y", call(call(var("f"), var("x")), var("y"))))));
+    }
+
+    #[test]
+    fn expands_functions_anywhere_in_the_expression() {
+        let e = let_("k", funcn(&["x", "y"], var("x")), call(funcn(&["a"], funcn(&["b", "c"], var("b"))), int(1)));
+        let expected = let_("k", func("x", func("y", var("x"))), call(func("a", func("b", func("c", var("b")))), int(1)));
+        assert_eq!(desugar(&e), *expected);
+    }
+
+    #[test]
+    fn comprehensions_map_over_the_list() {
+        let generator = |name: &str, list| Qualifier::Generator { var: name.to_string(), list };
+        let add = || binop(BinOp::Add, var("x"), var("y"));
+        let e = desugar_comprehension(add(), &[generator("x", var("xs"))]);
+        assert_eq!(e, *call(call(var("map"), func("x", add())), var("xs")));
+
+        let e = desugar_comprehension(
+            add(),
+            &[generator("x", var("xs")), generator("y", var("ys")), Qualifier::Guard(var("c"))],
+        );
+        let ys = call(call(var("filter"), func("y", var("c"))), var("ys"));
+        let inner = call(call(var("map"), func("y", add())), ys);
+        assert_eq!(e, *call(call(var("concatMap"), func("x", inner)), var("xs")));
+    }
+}
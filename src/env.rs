@@ -0,0 +1,362 @@
+//! The environment of bindings and the context that inference threads through
+
+use crate::error::TypeError;
+use crate::types::{free_type_vars, Constructor, DataType, Type, TypeScheme};
+use crate::unify::{appl_subs_to_scheme, appl_subs_to_type, Substitution};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub struct Env(pub HashMap<String, TypeScheme>);
+
+impl Env {
+    /// Return an intially filled environment
+    pub fn intial() -> Env {
+        let mut env = Env(Default::default());
+        // `&&` and `||` are operators of the syntax, only the negation is a function
+        let bool_type = || Box::new(Type::TNamed{name: "Bool".to_string()});
+        env.0.insert("not".to_string(), TypeScheme::mono(Box::new(Type::TFun{from: bool_type(), to: bool_type()})));
+
+        // The list functions work for any element type a
+        let a = || Box::new(Type::TVar{name: "a".to_string()});
+        let list_scheme = |type_| TypeScheme { vars: vec!["a".to_string()], type_ };
+        let cons = Box::new(Type::TFun{from: a(), to: Box::new(Type::TFun{from: Type::list(a()), to: Type::list(a())})});
+        env.0.insert("cons".to_string(), list_scheme(cons));
+        env.0.insert("head".to_string(), list_scheme(Box::new(Type::TFun{from: Type::list(a()), to: a()})));
+        env.0.insert("tail".to_string(), list_scheme(Box::new(Type::TFun{from: Type::list(a()), to: Type::list(a())})));
+
+        // The list comprehensions are made of map, filter and concatMap
+        let b = || Box::new(Type::TVar{name: "b".to_string()});
+        let fun = |from, to| Box::new(Type::TFun{from, to});
+        let ab_scheme = |type_| TypeScheme { vars: vec!["a".to_string(), "b".to_string()], type_ };
+        // map : (a -> b) -> List a -> List b
+        let map = fun(fun(a(), b()), fun(Type::list(a()), Type::list(b())));
+        env.0.insert("map".to_string(), ab_scheme(map));
+        // filter : (a -> Bool) -> List a -> List a
+        let filter = fun(fun(a(), bool_type()), fun(Type::list(a()), Type::list(a())));
+        env.0.insert("filter".to_string(), list_scheme(filter));
+        // concatMap : (a -> List b) -> List a -> List b
+        let concat_map = fun(fun(a(), Type::list(b())), fun(Type::list(a()), Type::list(b())));
+        env.0.insert("concatMap".to_string(), ab_scheme(concat_map));
+        env
+    }
+
+    /// Add the combinators const, flip and apply, their schemes quantify over several
+    /// variables, which makes them good at exercising generalization and instantiation
+    pub fn with_combinators(mut self) -> Env {
+        let var = |name: &str| Box::new(Type::TVar{name: name.to_string()});
+        let fun = |from, to| Box::new(Type::TFun{from, to});
+        let scheme = |vars: &[&str], type_| TypeScheme { vars: vars.iter().map(|name| name.to_string()).collect(), type_ };
+
+        // const : a -> b -> a
+        let const_ = fun(var("a"), fun(var("b"), var("a")));
+        self.0.insert("const".to_string(), scheme(&["a", "b"], const_));
+        // flip : (a -> b -> c) -> b -> a -> c
+        let flip = fun(fun(var("a"), fun(var("b"), var("c"))), fun(var("b"), fun(var("a"), var("c"))));
+        self.0.insert("flip".to_string(), scheme(&["a", "b", "c"], flip));
+        // apply : (a -> b) -> a -> b
+        let apply = fun(fun(var("a"), var("b")), fun(var("a"), var("b")));
+        self.0.insert("apply".to_string(), scheme(&["a", "b"], apply));
+        self
+    }
+
+    /// Add index and range, which use strings and characters together with integers and lists
+    pub fn with_strings(mut self) -> Env {
+        let named = |name: &str| Box::new(Type::TNamed{name: name.to_string()});
+        let fun = |from, to| Box::new(Type::TFun{from, to});
+
+        // index : String -> Int -> Char
+        let index = fun(named("String"), fun(named("Int"), named("Char")));
+        self.0.insert("index".to_string(), TypeScheme::mono(index));
+        // range : Char -> Char -> List Char
+        let range = fun(named("Char"), fun(named("Char"), Type::list(named("Char"))));
+        self.0.insert("range".to_string(), TypeScheme::mono(range));
+        self
+    }
+}
+
+/// The types that are known without a declaration
+const BUILTIN_TYPES: [&str; 5] = ["Int", "Bool", "String", "Char", "List"];
+
+/// Bindings of type variables that the copies of a context add to together
+pub type SharedBindings = Rc<RefCell<Vec<(String, Box<Type>)>>>;
+
+#[derive(Clone, Debug)]
+pub struct Context {
+    pub next: i32,
+    // next type variable to be generated
+    pub env: Env, // mapping of variable scopes to types
+    pub prefix: String, // prefix of the generated type variable names
+    pub data_types: Vec<DataType>, // declared sum types, their constructors can be used in expressions
+    pub var_aliases: HashMap<String, String>, // names to print instead of the generated type variable names
+    // Without the occurs check, the bindings unification made. Every copy of the context shares them,
+    // so they can be scanned for cycles once the whole expression is inferred
+    pub deferred_bindings: Option<SharedBindings>,
+}
+
+impl Context {
+
+    pub fn new(env: Env) -> Context {
+        Context {
+            next: 0,
+            env,
+            prefix: "T".to_string(),
+            data_types: Vec::new(),
+            var_aliases: HashMap::new(),
+            deferred_bindings: None,
+        }
+    }
+
+    /// Use a different prefix for the generated type variables, so the variables of
+    /// separate inference phases (eg. P0 for the prelude, U0 for user code) can be told apart
+    pub fn with_prefix(mut self, prefix: &str) -> Context {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Print the given generated type variables under a readable name, eg. `elem` for `T0`,
+    /// the other variables are still printed as a, b, ...
+    pub fn with_var_aliases(mut self, aliases: HashMap<String, String>) -> Context {
+        self.var_aliases = aliases;
+        self
+    }
+
+    /// Leave out the occurs check when binding type variables, the bindings are collected in the
+    /// given list instead, see `infer_with_deferred_occurs_check`
+    pub fn with_deferred_bindings(mut self, bindings: SharedBindings) -> Context {
+        self.deferred_bindings = Some(bindings);
+        self
+    }
+
+    /// Declare a sum type so its constructors can be used
+    pub fn with_data_type(mut self, data_type: DataType) -> Context {
+        self.data_types.push(data_type);
+        self
+    }
+
+    /// Declare a sum type after checking it. A type name can only be declared once and not be one
+    /// of the built in types, the values that already have the type would otherwise get the new
+    /// constructors. The values a constructor holds can only use the parameters as type variables,
+    /// otherwise a match could take out a value of any type. And a constructor name can only be used once
+    pub fn declare_data_type(&mut self, data_type: DataType) -> Result<(), TypeError> {
+        if BUILTIN_TYPES.contains(&data_type.name.as_str()) {
+            return Err(TypeError::BuiltinTypeName(data_type.name.clone()));
+        }
+        if self.data_types.iter().any(|declared| declared.name == data_type.name) {
+            return Err(TypeError::DuplicateDataType(data_type.name.clone()));
+        }
+        for (idx, param) in data_type.params.iter().enumerate() {
+            if data_type.params[..idx].contains(param) {
+                let name = param.clone();
+                return Err(TypeError::DuplicateTypeParameter { name, data_type: data_type.name.clone() });
+            }
+        }
+
+        for (idx, constructor) in data_type.constructors.iter().enumerate() {
+            let unbound = constructor
+                .fields
+                .iter()
+                .flat_map(free_type_vars)
+                .find(|name| !data_type.params.contains(name));
+            if let Some(name) = unbound {
+                return Err(TypeError::UnboundTypeVariable { name, data_type: data_type.name.clone() });
+            }
+
+            let earlier = data_type.constructors[..idx].iter().any(|other| other.name == constructor.name);
+            let declared = self.lookup_constructor(&constructor.name).map(|(declared, _)| declared.name.clone());
+            if earlier || declared.is_some() {
+                return Err(TypeError::DuplicateConstructor {
+                    name: constructor.name.clone(),
+                    data_type: declared.unwrap_or_else(|| data_type.name.clone()),
+                });
+            }
+        }
+
+        self.data_types.push(data_type);
+        Ok(())
+    }
+
+    /// Declare `Result a b = Ok a | Err b` for computations that can fail,
+    /// both parameters are quantified so `Ok 1` is a Result Int b for any b
+    pub fn with_result(self) -> Context {
+        let var = |name: &str| Box::new(Type::TVar { name: name.to_string() });
+        self.with_data_type(DataType {
+            name: "Result".to_string(),
+            params: vec!["a".to_string(), "b".to_string()],
+            constructors: vec![
+                Constructor { name: "Ok".to_string(), fields: vec![var("a")] },
+                Constructor { name: "Err".to_string(), fields: vec![var("b")] },
+            ],
+        })
+    }
+
+    /// Find the constructor with the given name and the data type it belongs to
+    pub fn lookup_constructor(&self, name: &str) -> Option<(&DataType, &Constructor)> {
+        self.data_types.iter().find_map(|data_type| {
+            data_type
+                .constructors
+                .iter()
+                .find(|constructor| constructor.name == name)
+                .map(|constructor| (data_type, constructor))
+        })
+    }
+}
+
+/// Add a binding to a contexts environment
+pub fn add_to_context(ctx: &Context, name: &str, type_: &Box<Type>) -> Context {
+    let mut new_context = ctx.clone();
+    new_context.env.0.insert(name.to_string(), TypeScheme::mono(type_.clone()));
+    new_context
+
+}
+
+/// Create a new type variable
+pub fn new_type_var(ctx: &mut Context) -> Box<Type> {
+    Box::new(Type::TVar {name: new_type_var_name(ctx)})
+}
+
+/// Create the name for a new type variable, for places that need the name itself like row variables
+pub fn new_type_var_name(ctx: &mut Context) -> String {
+    let idx = ctx.next;
+    ctx.next += 1;
+    format!("{}{}", ctx.prefix, idx)
+}
+
+/// Collect the type variables that are free in the environment, so the ones that are
+/// not quantified by the scheme of the binding they occur in
+pub fn free_type_vars_in_env(env: &Env) -> HashSet<String> {
+    env.0
+        .values()
+        .flat_map(|scheme| {
+            free_type_vars(&scheme.type_)
+                .into_iter()
+                .filter(move |name| !scheme.vars.contains(name))
+        })
+        .collect()
+}
+
+/// Quantify over the type variables of the type that are not free in the environment,
+/// those are not constrained by anything else, so every use may pick its own type for them
+pub fn generalize(env: &Env, type_: &Box<Type>) -> TypeScheme {
+    let env_vars = free_type_vars_in_env(env);
+    let vars = free_type_vars(type_)
+        .into_iter()
+        .filter(|name| !env_vars.contains(name))
+        .collect();
+    TypeScheme { vars, type_: type_.clone() }
+}
+
+/// Replace the quantified variables of the scheme with fresh type variables
+pub fn instantiate(ctx: &mut Context, scheme: &TypeScheme) -> Box<Type> {
+    let mut subst = Substitution::new();
+    for name in scheme.vars.iter() {
+        subst.0.insert(name.clone(), new_type_var(ctx));
+    }
+    appl_subs_to_type(&subst, &scheme.type_)
+}
+
+/// apply given substitution to each type in the context's environment
+/// Doesn't change the input context, but returns a new one
+pub fn apply_subs_to_ctx(subs: &Substitution, ctx: &Context) -> Context {
+    // Build the environment from the substituted bindings directly instead of
+    // cloning it first and then overwriting every entry
+    let env = ctx.env.0
+        .iter()
+        .map(|(name, scheme)| (name.clone(), appl_subs_to_scheme(subs, scheme)))
+        .collect();
+
+    Context {
+        next: ctx.next,
+        env: Env(env),
+        prefix: ctx.prefix.clone(),
+        data_types: ctx.data_types.clone(),
+        var_aliases: ctx.var_aliases.clone(),
+        deferred_bindings: ctx.deferred_bindings.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infer::infer;
+    use crate::test_util::{
+        app_type, bool, call, constructor, fun_type, func, int, list, list_type, match_, named, pcon, pvar, tuple,
+        tuple_type, tvar, var,
+    };
+    use crate::types::alpha_equivalent;
+
+    #[test]
+    fn generalize_keeps_variables_of_the_environment_free() {
+        let ctx = add_to_context(&Context::new(Env::intial()), "y", &tvar("a"));
+        let scheme = generalize(&ctx.env, &fun_type(tvar("b"), tvar("a")));
+        assert_eq!(scheme.vars, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn instantiate_gives_fresh_variables() {
+        let mut ctx = Context::new(Env::intial());
+        let scheme = TypeScheme { vars: vec!["a".to_string()], type_: fun_type(tvar("a"), tvar("a")) };
+        assert_eq!(instantiate(&mut ctx, &scheme), fun_type(tvar("T0"), tvar("T0")));
+        assert_eq!(instantiate(&mut ctx, &scheme), fun_type(tvar("T1"), tvar("T1")));
+    }
+
+    #[test]
+    fn prefixes_keep_phases_apart() {
+        let mut prelude = Context::new(Env::intial()).with_prefix("P");
+        let mut user = Context::new(Env::intial()).with_prefix("U");
+        assert_eq!(new_type_var(&mut prelude), tvar("P0"));
+        assert_eq!(new_type_var(&mut user), tvar("U0"));
+    }
+
+    #[test]
+    fn apply_subs_to_ctx_substitutes_every_binding() {
+        let mut ctx = add_to_context(&Context::new(Env::intial()), "x", &tvar("a"));
+        ctx.next = 3;
+        let mut subs = Substitution::new();
+        subs.0.insert("a".to_string(), named("Int"));
+
+        let applied = apply_subs_to_ctx(&subs, &ctx);
+        assert_eq!(applied.env.0["x"].type_, named("Int"));
+        assert_eq!(applied.env.0["not"].type_, fun_type(named("Bool"), named("Bool")));
+        assert_eq!(applied.next, 3);
+    }
+
+    #[test]
+    fn result_constructors_are_polymorphic_in_both_parameters() {
+        let mut ctx = Context::new(Env::intial()).with_result();
+        let result = |ok, err| app_type("Result", vec![ok, err]);
+
+        let (ok, _subs) = infer(&mut ctx, &constructor("Ok", vec![int(1)])).unwrap();
+        assert!(alpha_equivalent(&ok, &result(named("Int"), tvar("b"))));
+        let (err, _subs) = infer(&mut ctx, &constructor("Err", vec![int(1)])).unwrap();
+        assert!(alpha_equivalent(&err, &result(tvar("a"), named("Int"))));
+
+        // \r -> match r with | Ok x -> x | Err e -> 0
+        let e = func("r", match_(var("r"), vec![
+            (pcon("Ok", vec![pvar("x")]), var("x")),
+            (pcon("Err", vec![pvar("e")]), int(0)),
+        ]));
+        let (type_, _subs) = infer(&mut ctx, &e).unwrap();
+        assert!(alpha_equivalent(&type_, &fun_type(result(named("Int"), tvar("b")), named("Int"))));
+    }
+
+    #[test]
+    fn combinators_instantiate_every_variable() {
+        let mut ctx = Context::new(Env::intial().with_combinators());
+        let mut infers = |e, expected: Box<Type>| {
+            let (type_, _subs) = infer(&mut ctx, &e).unwrap();
+            assert!(alpha_equivalent(&type_, &expected), "{:?}", type_);
+        };
+        infers(call(call(var("const"), int(1)), bool(true)), named("Int"));
+        infers(call(var("const"), bool(true)), fun_type(tvar("b"), named("Bool")));
+        infers(call(var("flip"), var("const")), fun_type(tvar("a"), fun_type(tvar("b"), tvar("b"))));
+        infers(call(call(call(var("flip"), var("cons")), list(vec![])), int(1)), list_type(named("Int")));
+        infers(call(call(var("apply"), var("not")), bool(true)), named("Bool"));
+        // Every use gets its own variables, so const can be used at two types at once
+        infers(
+            tuple(vec![call(call(var("const"), int(1)), bool(true)), call(call(var("const"), bool(true)), int(1))]),
+            tuple_type(vec![named("Int"), named("Bool")]),
+        );
+    }
+}
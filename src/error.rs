@@ -0,0 +1,220 @@
+//! The errors that parsing and inference report
+
+use crate::types::{display_truncated, Type};
+use std::fmt;
+
+/// The reasons why inference or unification can fail
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeError {
+    // A variable is used that is not in the environment
+    UnboundVariable(String),
+    // Binding the type variable would create an infinite type like a = a -> b
+    OccursCheck {
+        name: String,
+        type_: Box<Type>,
+    },
+    // Two types that should be the same do not fit together
+    Mismatch {
+        expected: Box<Type>,
+        found: Box<Type>,
+    },
+    // A field is accessed or required that the record does not have
+    MissingField {
+        field: String,
+        record: Box<Type>,
+    },
+    // A field is accessed on something that is not known to be a record
+    NotARecord {
+        field: String,
+        found: Box<Type>,
+    },
+    // The inferred type of an expression does not fit the type it is annotated with
+    AnnotationMismatch {
+        annotated: Box<Type>,
+        inferred: Box<Type>,
+    },
+    // A record literal gives the same field more than once
+    DuplicateField(String),
+    // A constructor is used that no declared data type has
+    UnknownConstructor(String),
+    // A pattern binds the same variable more than once like `(x, x)`
+    DuplicatePatternVariable(String),
+    // A constructor is given more values than it holds
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    // A constructor of a data type holds a type variable that is not one of its parameters
+    UnboundTypeVariable {
+        name: String,
+        data_type: String,
+    },
+    // A constructor is declared that another data type already has
+    DuplicateConstructor {
+        name: String,
+        data_type: String,
+    },
+    // A data type is declared with the name of one that is already declared
+    DuplicateDataType(String),
+    // A data type is declared with the name of a built in type like Int
+    BuiltinTypeName(String),
+    // A data type has the same parameter more than once like `data P a a = C a`
+    DuplicateTypeParameter {
+        name: String,
+        data_type: String,
+    },
+    // An element is projected from something that is not a tuple
+    NotATuple {
+        index: usize,
+        found: Box<Type>,
+    },
+    // An element is projected that is past the end of the tuple
+    TupleIndexOutOfRange {
+        index: usize,
+        tuple: Box<Type>,
+    },
+    // A type is applied to an expression whose type has no quantified variable left like `1 @Int`
+    NotPolymorphic(Box<Type>),
+    // A type name is given a different number of arguments than it has parameters like `List Int Bool`
+    KindMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    // The source text did not parse, so there is nothing to infer
+    Parse(ParseError),
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::UnboundVariable(name) => write!(f, "Unbound variable {}", name),
+            TypeError::OccursCheck { name, type_ } => write!(
+                f,
+                "Type variable {} occurs in {}, this would be an infinite type",
+                name,
+                display_truncated(type_, usize::MAX)
+            ),
+            TypeError::Mismatch { expected, found } => write!(
+                f,
+                "Type mismatch expected: {}, but found: {}",
+                display_truncated(expected, usize::MAX),
+                display_truncated(found, usize::MAX)
+            ),
+            TypeError::MissingField { field, record } => write!(
+                f,
+                "Record {} has no field {}",
+                display_truncated(record, usize::MAX),
+                field
+            ),
+            TypeError::NotARecord { field, found } => write!(
+                f,
+                "Can not access field {} of {}, it is not a record",
+                field,
+                display_truncated(found, usize::MAX)
+            ),
+            TypeError::AnnotationMismatch { annotated, inferred } => write!(
+                f,
+                "Expression is annotated as {}, but its type is {}",
+                display_truncated(annotated, usize::MAX),
+                display_truncated(inferred, usize::MAX)
+            ),
+            TypeError::DuplicateField(field) => write!(f, "Field {} is given more than once", field),
+            TypeError::UnknownConstructor(name) => write!(f, "Unknown constructor {}", name),
+            TypeError::DuplicatePatternVariable(name) => write!(f, "Variable {} is bound more than once in a pattern", name),
+            TypeError::ArityMismatch { name, expected, found } => write!(
+                f,
+                "Constructor {} takes {} arguments, but is given {}",
+                name, expected, found
+            ),
+            TypeError::UnboundTypeVariable { name, data_type } => {
+                write!(f, "Type variable {} is not a parameter of {}", name, data_type)
+            }
+            TypeError::DuplicateConstructor { name, data_type } => {
+                write!(f, "Constructor {} is already declared by {}", name, data_type)
+            }
+            TypeError::DuplicateDataType(name) => write!(f, "Type {} is already declared", name),
+            TypeError::BuiltinTypeName(name) => write!(f, "Type {} is built in and can not be declared", name),
+            TypeError::DuplicateTypeParameter { name, data_type } => {
+                write!(f, "Type parameter {} is used more than once by {}", name, data_type)
+            }
+            TypeError::NotATuple { index, found } => write!(
+                f,
+                "Can not take element {} of {}, it is not a tuple",
+                index,
+                display_truncated(found, usize::MAX)
+            ),
+            TypeError::TupleIndexOutOfRange { index, tuple } => write!(
+                f,
+                "Tuple {} has no element {}",
+                display_truncated(tuple, usize::MAX),
+                index
+            ),
+            TypeError::NotPolymorphic(type_) => write!(
+                f,
+                "Can not apply a type to an expression of type {}, it is not polymorphic",
+                display_truncated(type_, usize::MAX)
+            ),
+            TypeError::KindMismatch { name, expected, found } => write!(
+                f,
+                "Type {} takes {} arguments, but is given {}",
+                name, expected, found
+            ),
+            TypeError::Parse(err) => write!(f, "Parse error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// The reasons why source text can not be parsed into an expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    // A character that is not part of any token
+    UnexpectedChar {
+        ch: char,
+        offset: usize,
+    },
+    // A number that does not fit in an integer
+    InvalidInt {
+        text: String,
+        offset: usize,
+    },
+    // A token that can not be used at this place
+    UnexpectedToken {
+        found: String,
+        expected: String,
+        offset: usize,
+    },
+    // The source ended while more was expected
+    UnexpectedEnd {
+        expected: String,
+    },
+    // A string or character literal that is not closed before the source ends
+    UnterminatedLiteral {
+        offset: usize,
+    },
+    // A character literal that does not hold a single character, or an escape that does not exist
+    InvalidChar {
+        text: String,
+        offset: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedChar { ch, offset } => write!(f, "Unexpected character '{}' at {}", ch, offset),
+            ParseError::InvalidInt { text, offset } => write!(f, "Invalid integer {} at {}", text, offset),
+            ParseError::UnexpectedToken { found, expected, offset } => {
+                write!(f, "Expected {}, but found '{}' at {}", expected, found, offset)
+            }
+            ParseError::UnexpectedEnd { expected } => write!(f, "Expected {}, but the input ended", expected),
+            ParseError::UnterminatedLiteral { offset } => write!(f, "Literal at {} is not closed", offset),
+            ParseError::InvalidChar { text, offset } => write!(f, "Invalid character literal {} at {}", text, offset),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
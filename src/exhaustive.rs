@@ -0,0 +1,317 @@
+//! Checks that the arms of a match cover every value and that every arm can be reached
+//!
+//! This follows "Warnings for pattern matching" by Luc Maranget: a pattern is useful after a
+//! list of earlier patterns if there is a value that it fits and none of the earlier ones do.
+//! An arm is unreachable if its pattern is not useful, and a match is exhaustive if the
+//! wildcard is not useful after all of its arms.
+
+use crate::ast::{Expression, Pattern, Warning};
+use crate::types::DataType;
+
+/// The outermost part of a pattern that is not a variable
+#[derive(Clone, Debug, PartialEq)]
+enum Head {
+    Constructor(String),
+    Tuple,
+    Int(i32),
+    Bool(bool),
+}
+
+/// A row of patterns, one for each value that is matched at the same time
+type Row = Vec<Pattern>;
+
+/// Report the matches in the expression that miss values or have arms that can never be used
+pub fn match_warnings(e: &Expression, data_types: &[DataType]) -> Vec<Warning> {
+    fn go(e: &Expression, data_types: &[DataType], warnings: &mut Vec<Warning>) {
+        match e {
+            Expression::EInt { .. }
+            | Expression::EBool { .. }
+            | Expression::EString { .. }
+            | Expression::EChar { .. }
+            | Expression::EVar { .. }
+            | Expression::EExtern { .. } => {}
+            Expression::EFunc { body, .. } | Expression::EFuncN { body, .. } => go(body, data_types, warnings),
+            Expression::ECall { func, arg } => {
+                go(func, data_types, warnings);
+                go(arg, data_types, warnings);
+            }
+            Expression::ETuple { elements } | Expression::EList { elements } | Expression::EConstructor { args: elements, .. } => {
+                for element in elements {
+                    go(element, data_types, warnings);
+                }
+            }
+            Expression::ERecord { fields } => {
+                for (_, value) in fields {
+                    go(value, data_types, warnings);
+                }
+            }
+            Expression::EFieldAccess { record, .. }
+            | Expression::ETupleProject { tuple: record, .. }
+            | Expression::EAnnot { expr: record, .. }
+            | Expression::ETypeApp { expr: record, .. }
+            | Expression::EComment { expr: record, .. } => {
+                go(record, data_types, warnings)
+            }
+            Expression::EMatch { scrutinee, arms } => {
+                go(scrutinee, data_types, warnings);
+                let mut rows: Vec<Row> = Vec::new();
+                for (pattern, body) in arms {
+                    let row = vec![pattern.clone()];
+                    if !useful(&rows, &row, data_types) {
+                        warnings.push(Warning::UnreachableArm(pattern.clone()));
+                    }
+                    rows.push(row);
+                    go(body, data_types, warnings);
+                }
+                if let Some(mut missing) = witness(&rows, 1, data_types) {
+                    warnings.push(Warning::NonExhaustiveMatch(missing.remove(0)));
+                }
+            }
+            Expression::EIf { cond, true_b, false_b } => {
+                go(cond, data_types, warnings);
+                go(true_b, data_types, warnings);
+                go(false_b, data_types, warnings);
+            }
+            Expression::EBinOp { lhs, rhs, .. } => {
+                go(lhs, data_types, warnings);
+                go(rhs, data_types, warnings);
+            }
+            Expression::EAssert { cond, body } => {
+                go(cond, data_types, warnings);
+                go(body, data_types, warnings);
+            }
+            Expression::ELet { value, body, .. }
+            | Expression::ELetRec { value, body, .. }
+            | Expression::ETry { expr: value, handler: body, .. } => {
+                go(value, data_types, warnings);
+                go(body, data_types, warnings);
+            }
+            Expression::ELetRecGroup { bindings, body } => {
+                for (_, value) in bindings {
+                    go(value, data_types, warnings);
+                }
+                go(body, data_types, warnings);
+            }
+            Expression::ELetPattern { pattern, value, body } => {
+                // A let has a single arm, its pattern has to fit every value
+                go(value, data_types, warnings);
+                if let Some(mut missing) = witness(&[vec![pattern.clone()]], 1, data_types) {
+                    warnings.push(Warning::NonExhaustiveMatch(missing.remove(0)));
+                }
+                go(body, data_types, warnings);
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    go(e, data_types, &mut warnings);
+    warnings
+}
+
+fn wildcard() -> Pattern {
+    Pattern::PVar { name: "_".to_string() }
+}
+
+/// Split a pattern into its head and the patterns below it, variables have no head
+fn head(p: &Pattern) -> Option<(Head, Vec<Pattern>)> {
+    match p {
+        Pattern::PVar { .. } => None,
+        Pattern::PInt { value } => Some((Head::Int(*value), Vec::new())),
+        Pattern::PBool { value } => Some((Head::Bool(*value), Vec::new())),
+        Pattern::PTuple { elements } => Some((Head::Tuple, elements.clone())),
+        Pattern::PConstructor { name, args } => Some((Head::Constructor(name.clone()), args.clone())),
+    }
+}
+
+/// Build a pattern back up from its head and the patterns below it
+fn rebuild(head: &Head, args: Vec<Pattern>) -> Pattern {
+    match head {
+        Head::Constructor(name) => Pattern::PConstructor { name: name.clone(), args },
+        Head::Tuple => Pattern::PTuple { elements: args },
+        Head::Int(value) => Pattern::PInt { value: *value },
+        Head::Bool(value) => Pattern::PBool { value: *value },
+    }
+}
+
+/// The distinct heads in the first column, with the number of patterns below them
+fn column_heads(rows: &[Row]) -> Vec<(Head, usize)> {
+    let mut heads: Vec<(Head, usize)> = Vec::new();
+    for (head, args) in rows.iter().filter_map(|row| head(&row[0])) {
+        if !heads.iter().any(|(seen, _)| *seen == head) {
+            heads.push((head, args.len()));
+        }
+    }
+    heads
+}
+
+/// Every head that values of the same type as the given head can have,
+/// or None when there are too many to list like for integers
+fn all_heads(head: &Head, arity: usize, data_types: &[DataType]) -> Option<Vec<(Head, usize)>> {
+    match head {
+        Head::Tuple => Some(vec![(Head::Tuple, arity)]),
+        Head::Bool(_) => Some(vec![(Head::Bool(false), 0), (Head::Bool(true), 0)]),
+        Head::Int(_) => None,
+        Head::Constructor(name) => data_types
+            .iter()
+            .find(|data_type| data_type.constructors.iter().any(|constructor| constructor.name == *name))
+            .map(|data_type| {
+                data_type
+                    .constructors
+                    .iter()
+                    .map(|constructor| (Head::Constructor(constructor.name.clone()), constructor.fields.len()))
+                    .collect()
+            }),
+    }
+}
+
+/// The heads of the type that the first column does not have yet, None if that can not be listed
+fn missing_heads(heads: &[(Head, usize)], data_types: &[DataType]) -> Option<Vec<(Head, usize)>> {
+    let (first, arity) = heads.first()?;
+    let all = all_heads(first, *arity, data_types)?;
+    Some(all.into_iter().filter(|(head, _)| !heads.iter().any(|(seen, _)| seen == head)).collect())
+}
+
+/// Keep the rows that fit the head, with the patterns below their head in place of the first one.
+/// A row with the same head but a different number of patterns below it does not type check,
+/// it is left out so that the rows keep the same width
+fn specialize(rows: &[Row], head: &Head, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut specialized = match self::head(&row[0]) {
+                None => vec![wildcard(); arity],
+                Some((row_head, args)) if row_head == *head && args.len() == arity => args,
+                Some(_) => return None,
+            };
+            specialized.extend(row[1..].iter().cloned());
+            Some(specialized)
+        })
+        .collect()
+}
+
+/// Keep the rows that start with a variable, without it
+fn default(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| head(&row[0]).is_none())
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Check if there is a value the row fits that none of the rows before it do
+fn useful(rows: &[Row], row: &[Pattern], data_types: &[DataType]) -> bool {
+    if row.is_empty() {
+        return rows.is_empty();
+    }
+    match head(&row[0]) {
+        Some((head, args)) => {
+            let arity = args.len();
+            let mut specialized_row = args;
+            specialized_row.extend(row[1..].iter().cloned());
+            useful(&specialize(rows, &head, arity), &specialized_row, data_types)
+        }
+        None => {
+            let heads = column_heads(rows);
+            match missing_heads(&heads, data_types) {
+                // The earlier rows list every head, so one of them has to miss something
+                Some(missing) if missing.is_empty() => heads.iter().any(|(head, arity)| {
+                    let mut specialized_row = vec![wildcard(); *arity];
+                    specialized_row.extend(row[1..].iter().cloned());
+                    useful(&specialize(rows, head, *arity), &specialized_row, data_types)
+                }),
+                // A value with a head that is not listed only fits the rows that start with a variable
+                _ => useful(&default(rows), &row[1..], data_types),
+            }
+        }
+    }
+}
+
+/// Find a row of `width` patterns that none of the rows fit, if there is one
+fn witness(rows: &[Row], width: usize, data_types: &[DataType]) -> Option<Row> {
+    if width == 0 {
+        return if rows.is_empty() { Some(Vec::new()) } else { None };
+    }
+    let heads = column_heads(rows);
+    match missing_heads(&heads, data_types) {
+        Some(missing) if missing.is_empty() => heads.iter().find_map(|(head, arity)| {
+            let mut found = witness(&specialize(rows, head, *arity), arity + width - 1, data_types)?;
+            let rest = found.split_off(*arity);
+            let mut row = vec![rebuild(head, found)];
+            row.extend(rest);
+            Some(row)
+        }),
+        missing => {
+            let mut found = witness(&default(rows), width - 1, data_types)?;
+            // Name a head that is not covered when there is one, otherwise any value is missing
+            let first = match missing.and_then(|missing| missing.into_iter().next()) {
+                Some((head, arity)) if !heads.is_empty() => rebuild(&head, vec![wildcard(); arity]),
+                _ => wildcard(),
+            };
+            found.insert(0, first);
+            Some(found)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{int, let_pattern, maybe_data_type, pbool, pcon, pint, ptuple, pvar, var};
+
+    fn check(arms: Vec<Pattern>) -> Vec<Warning> {
+        let arms = arms.into_iter().map(|pattern| (pattern, int(1))).collect();
+        let e = Expression::EMatch { scrutinee: var("x"), arms };
+        match_warnings(&e, &[maybe_data_type()])
+    }
+
+    #[test]
+    fn complete_matches_have_no_warnings() {
+        assert!(check(vec![pcon("Nothing", vec![]), pcon("Just", vec![pvar("y")])]).is_empty());
+        assert!(check(vec![ptuple(vec![pbool(true), pvar("_")]), ptuple(vec![pbool(false), pvar("y")])]).is_empty());
+        assert!(check(vec![pint(0), pvar("n")]).is_empty());
+    }
+
+    #[test]
+    fn reports_a_missing_pattern() {
+        assert_eq!(
+            check(vec![pcon("Just", vec![pvar("y")])]),
+            vec![Warning::NonExhaustiveMatch(pcon("Nothing", vec![]))]
+        );
+        assert_eq!(
+            check(vec![pcon("Nothing", vec![]), pcon("Just", vec![pbool(true)])]),
+            vec![Warning::NonExhaustiveMatch(pcon("Just", vec![pbool(false)]))]
+        );
+        assert_eq!(check(vec![pint(0), pint(1)]), vec![Warning::NonExhaustiveMatch(pvar("_"))]);
+        assert_eq!(check(vec![]), vec![Warning::NonExhaustiveMatch(pvar("_"))]);
+    }
+
+    #[test]
+    fn reports_unreachable_arms() {
+        assert_eq!(
+            check(vec![pvar("y"), pcon("Nothing", vec![])]),
+            vec![Warning::UnreachableArm(pcon("Nothing", vec![]))]
+        );
+        let both = ptuple(vec![pbool(true), pbool(false)]);
+        assert_eq!(
+            check(vec![ptuple(vec![pbool(true), pvar("_")]), both.clone(), ptuple(vec![pbool(false), pvar("_")])]),
+            vec![Warning::UnreachableArm(both)]
+        );
+    }
+
+    #[test]
+    fn patterns_of_different_arity_do_not_panic() {
+        // These do not type check, but the analysis can be asked about them anyway
+        check(vec![ptuple(vec![pvar("a"), pvar("b")]), ptuple(vec![pvar("c"), pvar("d"), pvar("e")])]);
+        check(vec![pcon("Just", vec![]), pcon("Just", vec![pvar("x")])]);
+        check(vec![pcon("Just", vec![pvar("x")]), pcon("Just", vec![]), pcon("Nothing", vec![])]);
+    }
+
+    #[test]
+    fn let_patterns_have_to_fit_every_value() {
+        let e = let_pattern(ptuple(vec![pvar("a"), pvar("b")]), var("x"), int(1));
+        assert!(match_warnings(&e, &[]).is_empty());
+        let e = let_pattern(ptuple(vec![pbool(true), pvar("b")]), var("x"), int(1));
+        assert_eq!(
+            match_warnings(&e, &[]),
+            vec![Warning::NonExhaustiveMatch(ptuple(vec![pbool(false), pvar("_")]))]
+        );
+    }
+}
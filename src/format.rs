@@ -0,0 +1,174 @@
+//! Canonical layout of source text, so the same program is always written the same way
+//!
+//! The keyword expressions each get their own lines:
+//!
+//! ```text
+//! let x = 1 in
+//! if x < 2
+//! then fun y -> y + x
+//! else match x with
+//!      | 1 -> 2
+//!      | _ -> x
+//! ```
+//!
+//! Everything else is printed on a single line with only the parentheses that are needed.
+
+use crate::ast::{precedence, Expression, Pattern};
+use crate::error::ParseError;
+use crate::parser::parse_with_comments;
+
+/// Parse the source and print it again in the canonical layout, comments included.
+/// Formatting the result again gives the same source
+pub fn format_source(src: &str) -> Result<String, ParseError> {
+    let expr = parse_with_comments(src)?;
+    Ok(layout(&expr, 0).join("\n"))
+}
+
+/// The lines of the expression in a place that needs at least the given precedence
+fn layout(e: &Expression, min: u8) -> Vec<String> {
+    if precedence(e) < min {
+        let mut lines = layout(e, 0);
+        lines[0].insert(0, '(');
+        lines.last_mut().unwrap().push(')');
+        return lines;
+    }
+    match e {
+        Expression::ELet { name, value, body } => let_lines(format!("let {} =", name), value, body),
+        Expression::ELetRec { name, value, body } => let_lines(format!("let rec {} =", name), value, body),
+        Expression::ELetRecGroup { bindings, body } => {
+            let mut lines = Vec::new();
+            let mut last_value = 0;
+            for (i, (name, value)) in bindings.iter().enumerate() {
+                let keyword = if i == 0 { "let rec" } else { "and" };
+                let value = layout(value, 0);
+                last_value = value.len();
+                lines.extend(hanging(format!("{} {} =", keyword, name), value));
+            }
+            // Like a single let, the `in` only gets its own line after a value of several lines
+            if last_value == 1 {
+                lines.last_mut().unwrap().push_str(" in");
+            } else {
+                lines.push("in".to_string());
+            }
+            lines.extend(layout(body, 0));
+            lines
+        }
+        Expression::ELetPattern { pattern, value, body } => {
+            // The parser knows this let by the parenthesis that follows it
+            let header = match pattern {
+                Pattern::PTuple { .. } => format!("let {} =", pattern),
+                _ => format!("let ({}) =", pattern),
+            };
+            let_lines(header, value, body)
+        }
+        Expression::EIf { cond, true_b, false_b } => {
+            let mut lines = after("if ", layout(cond, 0));
+            lines.extend(after("then ", layout(true_b, 0)));
+            lines.extend(after("else ", layout(false_b, 0)));
+            lines
+        }
+        Expression::EMatch { scrutinee, arms } => {
+            let mut lines = after("match ", layout(scrutinee, 0));
+            lines.last_mut().unwrap().push_str(" with");
+            for (i, (pattern, body)) in arms.iter().enumerate() {
+                // A match in an arm that is not the last would take the arms that follow
+                let min = if i + 1 < arms.len() { 1 } else { 0 };
+                lines.extend(hanging(format!("| {} ->", pattern), layout(body, min)));
+            }
+            lines
+        }
+        Expression::EFunc { param, body } => hanging(format!("fun {} ->", param), layout(body, 0)),
+        Expression::EFuncN { params, body } => hanging(format!("fun {} ->", params.join(" ")), layout(body, 0)),
+        Expression::EAssert { cond, body } => {
+            let mut lines = after("assert ", layout(cond, 0));
+            lines.last_mut().unwrap().push_str(" in");
+            lines.extend(layout(body, 0));
+            lines
+        }
+        Expression::EComment { comments, expr } => {
+            let mut lines: Vec<String> = comments.iter().map(|comment| format!("-- {}", comment)).collect();
+            lines.extend(layout(expr, min));
+            lines
+        }
+        _ => e.to_string().lines().map(|line| line.to_string()).collect(),
+    }
+}
+
+/// `let name = value in` followed by the body on the next line,
+/// a value of several lines goes on its own lines between the `=` and the `in`
+fn let_lines(header: String, value: &Expression, body: &Expression) -> Vec<String> {
+    let value = layout(value, 0);
+    let mut lines = if value.len() == 1 {
+        vec![format!("{} {} in", header, value[0])]
+    } else {
+        let mut lines = vec![header];
+        lines.extend(indent(2, value));
+        lines.push("in".to_string());
+        lines
+    };
+    lines.extend(layout(body, 0));
+    lines
+}
+
+/// Put the first line after the prefix and line the others up with it
+fn after(prefix: &str, lines: Vec<String>) -> Vec<String> {
+    let mut lines = lines.into_iter();
+    let first = format!("{}{}", prefix, lines.next().unwrap_or_default());
+    std::iter::once(first).chain(indent(prefix.len(), lines.collect())).collect()
+}
+
+/// Put a single line after the header, or several lines indented below it
+fn hanging(header: String, lines: Vec<String>) -> Vec<String> {
+    if lines.len() == 1 {
+        return vec![format!("{} {}", header, lines[0])];
+    }
+    std::iter::once(header).chain(indent(2, lines)).collect()
+}
+
+fn indent(width: usize, lines: Vec<String>) -> Vec<String> {
+    lines.into_iter().map(|line| format!("{}{}", " ".repeat(width), line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_keyword_expressions_on_their_own_lines() {
+        let src = "let x = 1 in if x<2 then fun y -> (y+x) else match x with 1 -> 2 | _ -> x";
+        let expected = "let x = 1 in\n\
+                        if x < 2\n\
+                        then fun y -> y + x\n\
+                        else match x with\n     \
+                             | 1 -> 2\n     \
+                             | _ -> x";
+        assert_eq!(format_source(src).unwrap(), expected);
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let src = "-- pick one\nlet rec f = fun n -> if n == 0 then -- done\n \
+                   (match m with | Just x -> (match x with | 1 -> true | _ -> false) | Nothing -> false) \
+                   else f (n - 1) in let (a, b) = (f 3, [1, 2]) in assert a in b";
+        let formatted = format_source(src).unwrap();
+        assert!(formatted.starts_with("-- pick one\nlet rec f =\n  fun n ->\n"), "{}", formatted);
+        assert!(formatted.contains("-- done"), "{}", formatted);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert_eq!(parse_with_comments(&formatted).unwrap(), parse_with_comments(src).unwrap());
+    }
+
+    #[test]
+    fn lays_out_every_binding_of_a_group() {
+        let src = "let rec f = fun n -> if n == 0 then true else g n and g = fun n -> f (n - 1) in f 3";
+        let expected = "let rec f =\n  \
+                          fun n ->\n    \
+                            if n == 0\n    \
+                            then true\n    \
+                            else g n\n\
+                        and g = fun n -> f (n - 1) in\n\
+                        f 3";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+}
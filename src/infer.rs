@@ -0,0 +1,1221 @@
+//! Inference of the type of an expression, algorithm W
+
+use crate::ast::{free_vars, BinOp, Expression, Pattern};
+use crate::desugar::desugar;
+use crate::env::{
+    add_to_context, apply_subs_to_ctx, generalize, instantiate, new_type_var, new_type_var_name, Context, Env,
+};
+use crate::error::TypeError;
+use crate::parser::parse;
+use crate::types::{alpha_equivalent, display_truncated, free_type_vars, is_ground, param_types, Type, TypeScheme};
+use crate::unify::{appl_subs_to_type, compose_substitution, find_cycle, unify_deferred, unify_with, Substitution};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// For an expression and an environment infer it's type
+pub fn infer(ctx: &mut Context, e: &Box<Expression>) -> Result<(Box<Type>, Substitution), TypeError> {
+    match e.deref() {
+        // An integer is just an integer
+        Expression::EInt { value: _ } => Ok((Box::new(Type::TNamed { name: "Int".to_string()}), Substitution::new())),
+        // And a boolean just a boolean
+        Expression::EBool { value: _ } => Ok((Box::new(Type::TNamed { name: "Bool".to_string()}), Substitution::new())),
+        // The literals of text are strings and characters
+        Expression::EString { .. } => Ok((Box::new(Type::TNamed { name: "String".to_string() }), Substitution::new())),
+        Expression::EChar { .. } => Ok((Box::new(Type::TNamed { name: "Char".to_string() }), Substitution::new())),
+        // For a variable look up it's scheme and instantiate it, so every use gets fresh variables
+        Expression::EVar { name } => {
+            let scheme = ctx.env
+                .0
+                .get(name)
+                .ok_or_else(|| TypeError::UnboundVariable(name.clone()))?
+                .clone();
+            Ok((instantiate(ctx, &scheme), Substitution::new()))
+        }
+        Expression::EFunc {param, body} => {
+            // Create a new type variable for the param
+            let new_type = new_type_var(ctx);
+            // Associate param with type variable, and extend the context,
+            // this creates a new context because it is local
+            let mut new_ctx = add_to_context(ctx, param, &new_type);
+            // Infer the types for the body, the local context shares the type variable counter
+            let (body_type, subst) = infer(&mut new_ctx, body)?;
+            ctx.next = new_ctx.next;
+            // Substitute the inferred type
+            let inferred_type = Box::new(Type::TFun {from: appl_subs_to_type(&subst, &new_type), to: body_type });
+            // Return the result
+            Ok((inferred_type, subst))
+        }
+        // Infer what it stands for, the nested single parameter functions
+        Expression::EFuncN { .. } => infer(ctx, &Box::new(desugar(e))),
+        Expression::ECall { func, arg } => {
+            let (func_type, s1) = infer(ctx, func)?;
+            let mut arg_ctx = apply_subs_to_ctx(&s1, ctx);
+            let (arg_type, s2) = infer(&mut arg_ctx, arg)?;
+            ctx.next = arg_ctx.next;
+
+            // The function has to take the argument type to some result type
+            let new_var = new_type_var(ctx);
+            let func_pre_unify = Box::new(Type::TFun { from: arg_type, to: new_var.clone() });
+            let s3 = unify_in(ctx, &appl_subs_to_type(&s2, &func_type), &func_pre_unify)?;
+
+            let result_subs = compose_substitution(&s3, &compose_substitution(&s2, &s1));
+            Ok((appl_subs_to_type(&s3, &new_var), result_subs))
+        }
+        Expression::ETuple { elements } => {
+            // Infer the elements from left to right, each with what the earlier ones taught us
+            let mut subs = Substitution::new();
+            let mut types = Vec::new();
+            for element in elements {
+                let mut element_ctx = apply_subs_to_ctx(&subs, ctx);
+                let (element_type, s) = infer(&mut element_ctx, element)?;
+                ctx.next = element_ctx.next;
+                subs = compose_substitution(&s, &subs);
+                types.push(element_type);
+            }
+            let elements = types.iter().map(|type_| appl_subs_to_type(&subs, type_)).collect();
+            Ok((Box::new(Type::TTuple { elements }), subs))
+        }
+        Expression::EList { elements } => {
+            // Every element has to have the same type, which starts out unknown so that `[]` is a List a
+            let mut element_type = new_type_var(ctx);
+            let mut subs = Substitution::new();
+            for element in elements {
+                let mut element_ctx = apply_subs_to_ctx(&subs, ctx);
+                let (type_, s1) = infer(&mut element_ctx, element)?;
+                ctx.next = element_ctx.next;
+                let s2 = unify_in(ctx, &appl_subs_to_type(&s1, &element_type), &type_)?;
+                subs = compose_substitution(&s2, &compose_substitution(&s1, &subs));
+                element_type = appl_subs_to_type(&subs, &element_type);
+            }
+            Ok((Type::list(element_type), subs))
+        }
+        Expression::ERecord { fields } => {
+            // Infer the field values from left to right, like the elements of a tuple
+            let mut subs = Substitution::new();
+            let mut types = BTreeMap::new();
+            for (field, value) in fields {
+                let mut value_ctx = apply_subs_to_ctx(&subs, ctx);
+                let (value_type, s) = infer(&mut value_ctx, value)?;
+                ctx.next = value_ctx.next;
+                subs = compose_substitution(&s, &subs);
+                if types.insert(field.clone(), value_type).is_some() {
+                    return Err(TypeError::DuplicateField(field.clone()));
+                }
+            }
+            let fields = types.iter().map(|(field, type_)| (field.clone(), appl_subs_to_type(&subs, type_))).collect();
+            Ok((Box::new(Type::TRecord { fields, rest: None }), subs))
+        }
+        Expression::EFieldAccess { record, field } => {
+            // The record can be any record that has at least this field, so { field: a | r }
+            let (record_type, s1) = infer(ctx, record)?;
+            if !matches!(record_type.deref(), Type::TVar { .. } | Type::TRecord { .. }) {
+                return Err(TypeError::NotARecord { field: field.clone(), found: record_type });
+            }
+            let field_type = new_type_var(ctx);
+            let rest = Some(new_type_var_name(ctx));
+            let mut fields = BTreeMap::new();
+            fields.insert(field.clone(), field_type.clone());
+            let s2 = unify_in(ctx, &record_type, &Box::new(Type::TRecord { fields, rest }))?;
+            Ok((appl_subs_to_type(&s2, &field_type), compose_substitution(&s2, &s1)))
+        }
+        Expression::ETupleProject { tuple, index } => {
+            // Like a field access, the tuple can be any tuple that has at least this element, so (_, a, ..r)
+            let (tuple_type, s1) = infer(ctx, tuple)?;
+            if !matches!(tuple_type.deref(), Type::TVar { .. } | Type::TTuple { .. } | Type::TOpenTuple { .. }) {
+                return Err(TypeError::NotATuple { index: *index, found: tuple_type });
+            }
+            let element_type = new_type_var(ctx);
+            let rest = new_type_var_name(ctx);
+            let mut elements = BTreeMap::new();
+            elements.insert(*index, element_type.clone());
+            let s2 = unify_in(ctx, &tuple_type, &Box::new(Type::TOpenTuple { elements, rest }))?;
+            Ok((appl_subs_to_type(&s2, &element_type), compose_substitution(&s2, &s1)))
+        }
+        Expression::EConstructor { name, args } => {
+            // The constructor is a curried function from the values it holds to its data type
+            let (data_type, constructor) = ctx
+                .lookup_constructor(name)
+                .ok_or_else(|| TypeError::UnknownConstructor(name.clone()))?;
+            if args.len() > constructor.fields.len() {
+                return Err(TypeError::ArityMismatch {
+                    name: name.clone(),
+                    expected: constructor.fields.len(),
+                    found: args.len(),
+                });
+            }
+            let scheme = data_type.constructor_scheme(constructor);
+            let mut type_ = instantiate(ctx, &scheme);
+
+            // Apply it to the values one at a time, each is inferred with what the earlier ones taught us
+            let mut subs = Substitution::new();
+            for arg in args {
+                let mut arg_ctx = apply_subs_to_ctx(&subs, ctx);
+                let (arg_type, s1) = infer(&mut arg_ctx, arg)?;
+                ctx.next = arg_ctx.next;
+                subs = compose_substitution(&s1, &subs);
+                if let Type::TFun { from, to } = appl_subs_to_type(&subs, &type_).deref() {
+                    let s2 = unify_in(ctx, from, &arg_type)?;
+                    subs = compose_substitution(&s2, &subs);
+                    type_ = appl_subs_to_type(&subs, to);
+                }
+            }
+            Ok((type_, subs))
+        }
+        Expression::EMatch { scrutinee, arms } => {
+            let (scrutinee_type, mut subs) = infer(ctx, scrutinee)?;
+            let mut result_type = new_type_var(ctx);
+            for (pattern, body) in arms {
+                // The pattern has to fit the scrutinee
+                let mut bindings = Vec::new();
+                let (pattern_type, s1) = infer_pattern(ctx, pattern, &mut bindings)?;
+                subs = compose_substitution(&s1, &subs);
+                let scrutinee = appl_subs_to_type(&subs, &scrutinee_type);
+                let s2 = unify_in(ctx, &scrutinee, &appl_subs_to_type(&subs, &pattern_type))?;
+                subs = compose_substitution(&s2, &subs);
+
+                // The body sees the variables of the pattern, these are not generalized
+                let mut arm_ctx = apply_subs_to_ctx(&subs, ctx);
+                for (name, type_) in bindings {
+                    arm_ctx.env.0.insert(name, TypeScheme::mono(appl_subs_to_type(&subs, &type_)));
+                }
+                let (body_type, s3) = infer(&mut arm_ctx, body)?;
+                ctx.next = arm_ctx.next;
+                subs = compose_substitution(&s3, &subs);
+
+                // Every arm results in the same type
+                let s4 = unify_in(ctx, &appl_subs_to_type(&subs, &result_type), &body_type)?;
+                subs = compose_substitution(&s4, &subs);
+                result_type = appl_subs_to_type(&subs, &result_type);
+            }
+            Ok((result_type, subs))
+        }
+        Expression::EIf { cond, true_b, false_b } => {
+            // The condition has to be a boolean
+            let (cond_type, s1) = infer(ctx, cond)?;
+            let s2 = unify_in(ctx, &Box::new(Type::TNamed { name: "Bool".to_string() }), &cond_type)?;
+            let subs = compose_substitution(&s2, &s1);
+
+            // Infer the branches with what we know so far, keeping the type variable counter in sync
+            let mut true_ctx = apply_subs_to_ctx(&subs, ctx);
+            let (true_type, s3) = infer(&mut true_ctx, true_b)?;
+            ctx.next = true_ctx.next;
+            let subs = compose_substitution(&s3, &subs);
+
+            let mut false_ctx = apply_subs_to_ctx(&subs, ctx);
+            let (false_type, s4) = infer(&mut false_ctx, false_b)?;
+            ctx.next = false_ctx.next;
+            let subs = compose_substitution(&s4, &subs);
+
+            // Both branches have to result in the same type
+            let s5 = unify_in(ctx, &appl_subs_to_type(&subs, &true_type), &false_type)?;
+            let subs = compose_substitution(&s5, &subs);
+            Ok((appl_subs_to_type(&subs, &false_type), subs))
+        }
+        Expression::EBinOp { op, lhs, rhs } => {
+            // The operator works like a call of a function of two arguments
+            let (lhs_param, rhs_param, result) = operator_type(ctx, *op);
+            let (lhs_type, s1) = infer(ctx, lhs)?;
+            let s2 = unify_in(ctx, &lhs_param, &lhs_type)?;
+            let subs = compose_substitution(&s2, &s1);
+
+            let mut rhs_ctx = apply_subs_to_ctx(&subs, ctx);
+            let (rhs_type, s3) = infer(&mut rhs_ctx, rhs)?;
+            ctx.next = rhs_ctx.next;
+            let subs = compose_substitution(&s3, &subs);
+            let s4 = unify_in(ctx, &appl_subs_to_type(&subs, &rhs_param), &rhs_type)?;
+            let subs = compose_substitution(&s4, &subs);
+            Ok((appl_subs_to_type(&subs, &result), subs))
+        }
+        Expression::EAnnot { expr, type_ } => {
+            // The variables in the annotation stand for any type, every annotation gets its own
+            let annotated = instantiate(ctx, &TypeScheme { vars: free_type_vars(type_), type_: type_.clone() });
+            let (inferred, s1) = infer(ctx, expr)?;
+            // Report the whole types instead of the part of them where unification failed
+            let s2 = unify_in(ctx, &annotated, &inferred).map_err(|_| TypeError::AnnotationMismatch {
+                annotated: type_.clone(),
+                inferred: inferred.clone(),
+            })?;
+            let subs = compose_substitution(&s2, &s1);
+            Ok((appl_subs_to_type(&subs, &annotated), subs))
+        }
+        Expression::ETypeApp { .. } => {
+            // Whatever quantified variables are left get fresh type variables
+            let (scheme, subs) = applied_scheme(ctx, e)?;
+            Ok((instantiate(ctx, &scheme), subs))
+        }
+        Expression::EAssert { cond, body } => {
+            // The asserted condition has to be a boolean
+            let (cond_type, s1) = infer(ctx, cond)?;
+            let s2 = unify_in(ctx, &Box::new(Type::TNamed { name: "Bool".to_string() }), &cond_type)?;
+            let subs = compose_substitution(&s2, &s1);
+
+            // The body is inferred with what the condition taught us
+            let mut body_ctx = apply_subs_to_ctx(&subs, ctx);
+            let (body_type, s3) = infer(&mut body_ctx, body)?;
+            ctx.next = body_ctx.next;
+            Ok((body_type, compose_substitution(&s3, &subs)))
+        }
+        Expression::ELet { name, value, body } => {
+            let (value_type, s1) = infer(ctx, value)?;
+            // The body sees the binding in the context that has what the value taught us applied,
+            // generalized so that it can be used at different types
+            let mut body_ctx = apply_subs_to_ctx(&s1, ctx);
+            let scheme = generalize(&body_ctx.env, &value_type);
+            body_ctx.env.0.insert(name.clone(), scheme);
+            let (body_type, s2) = infer(&mut body_ctx, body)?;
+            ctx.next = body_ctx.next;
+            Ok((body_type, compose_substitution(&s2, &s1)))
+        }
+        Expression::ELetRec { name, value, body } => {
+            // While inferring the value the name is bound to a fresh type variable,
+            // the uses inside of the value are monomorphic
+            let rec_type = new_type_var(ctx);
+            let mut value_ctx = add_to_context(ctx, name, &rec_type);
+            let (value_type, s1) = infer(&mut value_ctx, value)?;
+            ctx.next = value_ctx.next;
+
+            // The recursive uses have to agree with what the value turned out to be
+            let s2 = unify_in(ctx, &appl_subs_to_type(&s1, &rec_type), &value_type)?;
+            let subs = compose_substitution(&s2, &s1);
+
+            // From here it works like a normal let
+            let mut body_ctx = apply_subs_to_ctx(&subs, ctx);
+            let scheme = generalize(&body_ctx.env, &appl_subs_to_type(&subs, &value_type));
+            body_ctx.env.0.insert(name.clone(), scheme);
+            let (body_type, s3) = infer(&mut body_ctx, body)?;
+            ctx.next = body_ctx.next;
+            Ok((body_type, compose_substitution(&s3, &subs)))
+        }
+        Expression::ELetRecGroup { bindings, body } => {
+            // Like a single let rec, but every name of the group is bound to its own fresh type variable
+            // while inferring the values, so they can use each other
+            let mut group_ctx = ctx.clone();
+            let rec_types: Vec<Box<Type>> = bindings.iter().map(|_| new_type_var(&mut group_ctx)).collect();
+            for ((name, _), rec_type) in bindings.iter().zip(&rec_types) {
+                group_ctx = add_to_context(&group_ctx, name, rec_type);
+            }
+
+            // Each value is inferred with what the ones before it taught us
+            let mut subs = Substitution::new();
+            for ((_, value), rec_type) in bindings.iter().zip(&rec_types) {
+                let mut value_ctx = apply_subs_to_ctx(&subs, &group_ctx);
+                let (value_type, s1) = infer(&mut value_ctx, value)?;
+                group_ctx.next = value_ctx.next;
+                let subs1 = compose_substitution(&s1, &subs);
+                let s2 = unify_in(&mut group_ctx, &appl_subs_to_type(&subs1, rec_type), &value_type)?;
+                subs = compose_substitution(&s2, &subs1);
+            }
+            ctx.next = group_ctx.next;
+
+            // The whole group is generalized at once, like the name of a normal let
+            let mut body_ctx = apply_subs_to_ctx(&subs, ctx);
+            let schemes: Vec<(String, TypeScheme)> = bindings
+                .iter()
+                .zip(&rec_types)
+                .map(|((name, _), rec_type)| {
+                    (name.clone(), generalize(&body_ctx.env, &appl_subs_to_type(&subs, rec_type)))
+                })
+                .collect();
+            body_ctx.env.0.extend(schemes);
+            let (body_type, s3) = infer(&mut body_ctx, body)?;
+            ctx.next = body_ctx.next;
+            Ok((body_type, compose_substitution(&s3, &subs)))
+        }
+        Expression::ELetPattern { pattern, value, body } => {
+            // The pattern has to fit the value, like the pattern of an arm fits the scrutinee
+            let (value_type, s1) = infer(ctx, value)?;
+            let mut bindings = Vec::new();
+            let (pattern_type, s2) = infer_pattern(ctx, pattern, &mut bindings)?;
+            let s3 = unify_in(ctx, &appl_subs_to_type(&s2, &value_type), &pattern_type)?;
+            let subs = compose_substitution(&s3, &compose_substitution(&s2, &s1));
+
+            // Unlike in an arm, the variables are generalized like the name of a let
+            let mut body_ctx = apply_subs_to_ctx(&subs, ctx);
+            let schemes: Vec<(String, TypeScheme)> = bindings
+                .into_iter()
+                .map(|(name, type_)| (name, generalize(&body_ctx.env, &appl_subs_to_type(&subs, &type_))))
+                .collect();
+            body_ctx.env.0.extend(schemes);
+            let (body_type, s4) = infer(&mut body_ctx, body)?;
+            ctx.next = body_ctx.next;
+            Ok((body_type, compose_substitution(&s4, &subs)))
+        }
+        Expression::ETry { expr, error, handler } => {
+            // The expression has to be a Result a b, the handler gets the b and makes an a
+            let (expr_type, s1) = infer(ctx, expr)?;
+            let value_type = new_type_var(ctx);
+            let error_type = new_type_var(ctx);
+            let result = Box::new(Type::TApp { name: "Result".to_string(), args: vec![value_type.clone(), error_type.clone()] });
+            let s2 = unify_in(ctx, &expr_type, &result)?;
+            let subs = compose_substitution(&s2, &s1);
+
+            // Like the variables of an arm, the error is not generalized
+            let mut handler_ctx = apply_subs_to_ctx(&subs, ctx);
+            handler_ctx.env.0.insert(error.clone(), TypeScheme::mono(appl_subs_to_type(&subs, &error_type)));
+            let (handler_type, s3) = infer(&mut handler_ctx, handler)?;
+            ctx.next = handler_ctx.next;
+            let subs = compose_substitution(&s3, &subs);
+
+            let s4 = unify_in(ctx, &appl_subs_to_type(&subs, &value_type), &handler_type)?;
+            let subs = compose_substitution(&s4, &subs);
+            Ok((appl_subs_to_type(&subs, &value_type), subs))
+        }
+        // There is nothing to infer, the value is trusted to have its scheme,
+        // which a let generalizes back to the same scheme
+        Expression::EExtern { ty } => Ok((instantiate(ctx, ty), Substitution::new())),
+        // Comments are only kept to print them again
+        Expression::EComment { expr, .. } => infer(ctx, expr),
+    }
+}
+
+/// The scheme of an expression that a type is applied to. A variable has the scheme of its binding,
+/// another expression is generalized like the value of a let. Each type application then fixes
+/// the first variable that is still quantified, so `const @Int @Bool` is `Int -> Bool -> Int`
+fn applied_scheme(ctx: &mut Context, e: &Box<Expression>) -> Result<(TypeScheme, Substitution), TypeError> {
+    match e.deref() {
+        Expression::ETypeApp { expr, ty } => {
+            let (TypeScheme { vars, type_ }, subs) = applied_scheme(ctx, expr)?;
+            check_kinds(ctx, ty)?;
+            let (first, rest) = vars.split_first().ok_or_else(|| TypeError::NotPolymorphic(type_.clone()))?;
+            // Like in an annotation the variables of the type stand for any type, and the variables
+            // that stay quantified get fresh names so the type can not capture them
+            let mut fixed = Substitution::new();
+            fixed.0.insert(first.clone(), instantiate(ctx, &TypeScheme { vars: free_type_vars(ty), type_: ty.clone() }));
+            let mut vars = Vec::new();
+            for var in rest {
+                let fresh = new_type_var_name(ctx);
+                fixed.0.insert(var.clone(), Box::new(Type::TVar { name: fresh.clone() }));
+                vars.push(fresh);
+            }
+            Ok((TypeScheme { vars, type_: appl_subs_to_type(&fixed, &type_) }, subs))
+        }
+        Expression::EVar { name } => {
+            let scheme = ctx.env.0.get(name).ok_or_else(|| TypeError::UnboundVariable(name.clone()))?;
+            Ok((scheme.clone(), Substitution::new()))
+        }
+        _ => {
+            let (type_, subs) = infer(ctx, e)?;
+            let scheme = generalize(&apply_subs_to_ctx(&subs, ctx).env, &type_);
+            Ok((scheme, subs))
+        }
+    }
+}
+
+/// Check that every type name in the type is given as many arguments as it has parameters,
+/// names that are not known are left for unification to reject
+fn check_kinds(ctx: &Context, ty: &Box<Type>) -> Result<(), TypeError> {
+    let arity = |name: &str| match name {
+        "Int" | "Bool" | "String" | "Char" => Some(0),
+        "List" => Some(1),
+        _ => ctx.data_types.iter().find(|data_type| data_type.name == name).map(|data_type| data_type.params.len()),
+    };
+    let check = |name: &str, found: usize| match arity(name) {
+        Some(expected) if expected != found => Err(TypeError::KindMismatch { name: name.to_string(), expected, found }),
+        _ => Ok(()),
+    };
+    match ty.deref() {
+        Type::TVar { .. } => Ok(()),
+        Type::TNamed { name } => check(name, 0),
+        Type::TApp { name, args } => {
+            check(name, args.len())?;
+            args.iter().try_for_each(|arg| check_kinds(ctx, arg))
+        }
+        Type::TFun { from, to } => {
+            check_kinds(ctx, from)?;
+            check_kinds(ctx, to)
+        }
+        Type::TTuple { elements } => elements.iter().try_for_each(|element| check_kinds(ctx, element)),
+        Type::TRecord { fields, .. } => fields.values().try_for_each(|field| check_kinds(ctx, field)),
+        Type::TOpenTuple { elements, .. } => elements.values().try_for_each(|element| check_kinds(ctx, element)),
+    }
+}
+
+/// Unify two types, the row variables that unification makes are named like the other type variables
+fn unify_in(ctx: &mut Context, t1: &Box<Type>, t2: &Box<Type>) -> Result<Substitution, TypeError> {
+    match ctx.deferred_bindings.clone() {
+        Some(bindings) => unify_deferred(t1, t2, &mut || new_type_var_name(ctx), &mut bindings.borrow_mut()),
+        None => unify_with(t1, t2, &mut || new_type_var_name(ctx)),
+    }
+}
+
+/// The types of the operands and the result of an operator, arithmetic is on integers,
+/// `&&` and `||` on booleans and equality works for any type as long as both sides agree
+fn operator_type(ctx: &mut Context, op: BinOp) -> (Box<Type>, Box<Type>, Box<Type>) {
+    let int = || Box::new(Type::TNamed { name: "Int".to_string() });
+    let bool = || Box::new(Type::TNamed { name: "Bool".to_string() });
+    match op {
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => (int(), int(), int()),
+        BinOp::Lt | BinOp::Gt => (int(), int(), bool()),
+        BinOp::And | BinOp::Or => (bool(), bool(), bool()),
+        BinOp::Eq => {
+            let operand = new_type_var(ctx);
+            (operand.clone(), operand, bool())
+        }
+    }
+}
+
+/// Infer the type of the values a pattern fits, and collect the variables it binds with their types
+fn infer_pattern(
+    ctx: &mut Context,
+    pattern: &Pattern,
+    bindings: &mut Vec<(String, Box<Type>)>,
+) -> Result<(Box<Type>, Substitution), TypeError> {
+    match pattern {
+        // The wildcard binds nothing, so it can be used more than once
+        Pattern::PVar { name } if name == "_" => Ok((new_type_var(ctx), Substitution::new())),
+        Pattern::PVar { name } => {
+            if bindings.iter().any(|(bound, _)| bound == name) {
+                return Err(TypeError::DuplicatePatternVariable(name.clone()));
+            }
+            let type_ = new_type_var(ctx);
+            bindings.push((name.clone(), type_.clone()));
+            Ok((type_, Substitution::new()))
+        }
+        Pattern::PInt { .. } => Ok((Box::new(Type::TNamed { name: "Int".to_string() }), Substitution::new())),
+        Pattern::PBool { .. } => Ok((Box::new(Type::TNamed { name: "Bool".to_string() }), Substitution::new())),
+        Pattern::PTuple { elements } => {
+            let mut subs = Substitution::new();
+            let mut types = Vec::new();
+            for element in elements {
+                let (type_, s) = infer_pattern(ctx, element, bindings)?;
+                subs = compose_substitution(&s, &subs);
+                types.push(type_);
+            }
+            let elements = types.iter().map(|type_| appl_subs_to_type(&subs, type_)).collect();
+            Ok((Box::new(Type::TTuple { elements }), subs))
+        }
+        Pattern::PConstructor { name, args } => {
+            // Unlike in an expression, a constructor pattern needs a pattern for each of its values
+            let (data_type, constructor) = ctx
+                .lookup_constructor(name)
+                .ok_or_else(|| TypeError::UnknownConstructor(name.clone()))?;
+            if args.len() != constructor.fields.len() {
+                return Err(TypeError::ArityMismatch {
+                    name: name.clone(),
+                    expected: constructor.fields.len(),
+                    found: args.len(),
+                });
+            }
+            let scheme = data_type.constructor_scheme(constructor);
+            let mut type_ = instantiate(ctx, &scheme);
+
+            let mut subs = Substitution::new();
+            for arg in args {
+                let (arg_type, s1) = infer_pattern(ctx, arg, bindings)?;
+                subs = compose_substitution(&s1, &subs);
+                if let Type::TFun { from, to } = appl_subs_to_type(&subs, &type_).deref() {
+                    let s2 = unify_in(ctx, from, &arg_type)?;
+                    subs = compose_substitution(&s2, &subs);
+                    type_ = appl_subs_to_type(&subs, to);
+                }
+            }
+            Ok((type_, subs))
+        }
+    }
+}
+
+/// Infer the expression in the initial environment and return the full substitution
+/// next to the type, for callers that want to drive their own elaboration
+///
+/// ```
+/// use typing_test::infer::infer_full;
+/// use typing_test::parser::parse;
+/// use typing_test::types::Type;
+///
+/// // The parameter gets the first type variable, T0, and `not` binds it to Bool
+/// let (_type, subs) = infer_full(&parse("fun x -> not x").unwrap()).unwrap();
+/// assert_eq!(subs.get("T0"), Some(&Box::new(Type::TNamed { name: "Bool".to_string() })));
+/// ```
+pub fn infer_full(expr: &Expression) -> Result<(Box<Type>, Substitution), TypeError> {
+    let mut ctx = Context::new(Env::intial());
+    infer(&mut ctx, &Box::new(expr.clone()))
+}
+
+/// Infer like `infer_full`, but without the occurs check at every binding of a type variable.
+/// All bindings are scanned for cycles once at the end instead, so an infinite type is still reported
+pub fn infer_with_deferred_occurs_check(expr: &Expression) -> Result<(Box<Type>, Substitution), TypeError> {
+    let bindings = Rc::new(RefCell::new(Vec::new()));
+    let mut ctx = Context::new(Env::intial()).with_deferred_bindings(bindings.clone());
+    let inferred = infer(&mut ctx, &Box::new(expr.clone()));
+    // A cycle was made before anything else went wrong, the occurs check would have stopped there
+    find_cycle(&bindings.borrow())?;
+    inferred
+}
+
+/// Parse the source and infer its type in the initial environment
+///
+/// ```
+/// use typing_test::infer::infer_str;
+/// use typing_test::types::Type;
+///
+/// let type_ = infer_str("fun x -> x").unwrap();
+/// assert!(matches!(*type_, Type::TFun { .. }));
+/// ```
+pub fn infer_str(src: &str) -> Result<Box<Type>, TypeError> {
+    let expr = parse(src).map_err(TypeError::Parse)?;
+    let (type_, _subs) = infer_full(&expr)?;
+    Ok(type_)
+}
+
+/// Infer every expression of a script, they are separated by `;;` and each is inferred
+/// on its own in the initial environment. Blank parts, like after a trailing `;;`, are skipped
+pub fn infer_file(src: &str) -> Vec<Result<Box<Type>, TypeError>> {
+    src.split(";;")
+        .filter(|part| !part.trim().is_empty())
+        .map(infer_str)
+        .collect()
+}
+
+/// Infer each expression on its own in the given environment, like a build tool checking many files.
+/// Inference does not change the environment of its context, so all of them share one context
+/// instead of cloning the environment for every expression
+pub fn infer_batch(exprs: &[Expression], env: &Env) -> Vec<Result<Box<Type>, TypeError>> {
+    let mut ctx = Context::new(env.clone());
+    exprs
+        .iter()
+        .map(|expr| {
+            // Every expression names its type variables from the start, like it was inferred alone
+            ctx.next = 0;
+            let (type_, _subs) = infer(&mut ctx, &Box::new(expr.clone()))?;
+            Ok(type_)
+        })
+        .collect()
+}
+
+/// Check if the expression has a single monomorphic type in the initial environment,
+/// eg. `1` has, but `fun x -> x` is polymorphic
+pub fn is_monomorphic(expr: &Expression) -> Result<bool, TypeError> {
+    let (type_, _subs) = infer_full(expr)?;
+    Ok(is_ground(&type_))
+}
+
+/// Check if two expressions have the same type in the initial environment,
+/// which lets a refactoring verify that it preserved the type
+pub fn same_type(e1: &Expression, e2: &Expression) -> Result<bool, TypeError> {
+    let (t1, _) = infer_full(e1)?;
+    let (t2, _) = infer_full(e2)?;
+    Ok(alpha_equivalent(&t1, &t2))
+}
+
+/// Report the type the expression requires of each of its free variables,
+/// eg. `f 1` requires `f : Int -> a`. This is the interface an environment needs to satisfy
+pub fn required_signatures(expr: &Expression) -> Result<Vec<(String, Box<Type>)>, TypeError> {
+    // Every free variable starts out as a fresh type variable
+    let mut ctx = Context::new(Env(Default::default()));
+    let mut assumed = Vec::new();
+    for name in free_vars(expr) {
+        let type_ = new_type_var(&mut ctx);
+        ctx = add_to_context(&ctx, &name, &type_);
+        assumed.push((name, type_));
+    }
+
+    // The constraints on the free variables are collected in the substitution
+    let (_type, subs) = infer(&mut ctx, &Box::new(expr.clone()))?;
+    Ok(assumed
+        .into_iter()
+        .map(|(name, type_)| (name, appl_subs_to_type(&subs, &type_)))
+        .collect())
+}
+
+/// Infer an expression where the free variables that are not in the environment
+/// become implicit parameters, so `x + 1` is inferred as `fun x -> x + 1`.
+/// Returns the type together with the order of the synthesized parameters
+pub fn infer_with_implicit_params(ctx: &mut Context, e: &Expression) -> Result<(Box<Type>, Vec<String>), TypeError> {
+    let params: Vec<String> = free_vars(e)
+        .into_iter()
+        .filter(|name| !ctx.env.0.contains_key(name))
+        .collect();
+
+    // Wrap from the inside out so the first parameter becomes the outermost function
+    let wrapped = params.iter().rev().fold(Box::new(e.clone()), |body, param| {
+        Box::new(Expression::EFunc { param: param.clone(), body })
+    });
+
+    let (type_, _subs) = infer(ctx, &wrapped)?;
+    Ok((type_, params))
+}
+
+/// Reasons why an inferred type can not be exported as a signature
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportError {
+    // The function takes another function as an argument
+    HigherOrder(Box<Type>),
+    // The expression does not type check to begin with
+    Type(TypeError),
+}
+
+/// Infer the expression and return its type if it is first order, so none of the
+/// arguments of the function are functions themselves. This is what FFI and codegen support
+pub fn first_order_signature(expr: &Expression) -> Result<Box<Type>, ExportError> {
+    let (type_, _subs) = infer_full(expr).map_err(ExportError::Type)?;
+    let higher_order = param_types(&type_)
+        .iter()
+        .any(|param| matches!(param.deref(), Type::TFun { .. }));
+
+    if higher_order {
+        Err(ExportError::HigherOrder(type_))
+    } else {
+        Ok(type_)
+    }
+}
+
+/// Format a typing result for verbose output: the type on the first line and the
+/// equations of the substitution below it, sorted by variable and without trivial ones
+pub fn format_result(ty: &Box<Type>, subst: &Substitution) -> String {
+    let mut equations: Vec<(&String, &Box<Type>)> = subst.0
+        .iter()
+        .filter(|(name, type_)| !matches!(type_.as_ref(), Type::TVar { name: other } if other == *name))
+        .collect();
+    equations.sort_by_key(|(name, _)| *name);
+
+    let mut lines = vec![display_truncated(ty, usize::MAX)];
+    for (name, type_) in equations {
+        lines.push(format!("  {} = {}", name, display_truncated(type_, usize::MAX)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_type;
+    use crate::test_util::{
+        annot, app_type, assert_infers, assert_type_error, binop, bool, call, constructor, extern_, field, fun_type, func,
+        if_, int, let_, let_pattern, list, list_type, match_, maybe_data_type, named, open_record_type, pbool, pcon, pint,
+        open_tuple_type, ptuple, pvar, record, record_type, tuple, tuple_type, tvar, var,
+    };
+
+    fn assert_infers_expr(e: &Expression, expected: &Box<Type>) {
+        let (type_, _subs) = infer_full(e).unwrap();
+        assert!(
+            alpha_equivalent(&type_, expected),
+            "inferred {} but expected {}",
+            display_truncated(&type_, usize::MAX),
+            display_truncated(expected, usize::MAX)
+        );
+    }
+
+    #[test]
+    fn infers_the_combinators() {
+        let (a, b, c) = (|| tvar("a"), || tvar("b"), || tvar("c"));
+        let zoo = vec![
+            ("I", func("x", var("x")), fun_type(a(), a())),
+            ("K", func("x", func("y", var("x"))), fun_type(a(), fun_type(b(), a()))),
+            (
+                "S",
+                func("x", func("y", func("z", call(call(var("x"), var("z")), call(var("y"), var("z")))))),
+                fun_type(fun_type(a(), fun_type(b(), c())), fun_type(fun_type(a(), b()), fun_type(a(), c()))),
+            ),
+            (
+                "B",
+                func("f", func("g", func("x", call(var("f"), call(var("g"), var("x")))))),
+                fun_type(fun_type(b(), c()), fun_type(fun_type(a(), b()), fun_type(a(), c()))),
+            ),
+            (
+                "C",
+                func("f", func("x", func("y", call(call(var("f"), var("y")), var("x"))))),
+                fun_type(fun_type(a(), fun_type(b(), c())), fun_type(b(), fun_type(a(), c()))),
+            ),
+            (
+                "W",
+                func("f", func("x", call(call(var("f"), var("x")), var("x")))),
+                fun_type(fun_type(a(), fun_type(a(), b())), fun_type(a(), b())),
+            ),
+        ];
+        for (name, e, expected) in zoo {
+            let (type_, _subs) = infer_full(&e).unwrap();
+            assert!(alpha_equivalent(&type_, &expected), "{} : {}", name, display_truncated(&type_, usize::MAX));
+        }
+    }
+
+    #[test]
+    fn booleans_do_not_need_the_environment() {
+        let mut ctx = Context::new(Env(Default::default()));
+        let (type_, _subs) = infer(&mut ctx, &bool(false)).unwrap();
+        assert_eq!(type_, named("Bool"));
+    }
+
+    #[test]
+    fn infers_tuples() {
+        let pair = func("x", func("y", tuple(vec![var("x"), var("y")])));
+        assert_infers_expr(&pair, &fun_type(tvar("a"), fun_type(tvar("b"), tuple_type(vec![tvar("a"), tvar("b")]))));
+
+        // What a later element learns about a variable shows up in the earlier element too
+        let e = func("f", tuple(vec![var("f"), call(var("f"), int(1)), tuple(vec![])]));
+        let f_type = fun_type(named("Int"), tvar("a"));
+        assert_infers_expr(&e, &fun_type(f_type.clone(), tuple_type(vec![f_type, tvar("a"), tuple_type(vec![])])));
+    }
+
+    #[test]
+    fn infers_lists() {
+        assert_infers_expr(&list(vec![int(1), int(2), int(3)]), &list_type(named("Int")));
+        assert_infers_expr(&list(vec![]), &list_type(tvar("a")));
+        assert_infers_expr(&func("x", list(vec![var("x"), int(1)])), &fun_type(named("Int"), list_type(named("Int"))));
+        assert!(infer_full(&list(vec![int(1), bool(true)])).is_err());
+    }
+
+    #[test]
+    fn infers_strings_and_characters() {
+        assert_infers("(\"abc\", 'a')", "(String, Char)");
+
+        let infer_with_strings = |src: &str| {
+            let mut ctx = Context::new(Env::intial().with_strings());
+            infer(&mut ctx, &Box::new(parse(src).unwrap())).map(|(type_, _subs)| type_)
+        };
+        assert_eq!(infer_with_strings("index \"abc\" 0"), Ok(named("Char")));
+        assert_eq!(infer_with_strings("range 'a' 'z'"), Ok(list_type(named("Char"))));
+        assert!(matches!(infer_with_strings("range \"a\" 'z'"), Err(TypeError::Mismatch { .. })));
+    }
+
+    #[test]
+    fn infers_list_comprehensions() {
+        assert_infers("[ x + 1 | x <- [1,2,3] ]", "List Int");
+        assert_infers("fun xs -> [ (x, true) | x <- xs ]", "List a -> List (a, Bool)");
+        assert_infers("[ (x, y) | x <- [1, 2], y <- [true], x < 2 ]", "List (Int, Bool)");
+        assert_infers("fun xs ys -> [ x | x <- xs, y <- ys, y ]", "List a -> List Bool -> List a");
+        assert!(infer_str("[ x | x <- [1], x ]").is_err());
+    }
+
+    #[test]
+    fn list_builtins_are_polymorphic() {
+        let e = call(call(var("cons"), int(1)), list(vec![]));
+        assert_infers_expr(&e, &list_type(named("Int")));
+        assert_infers_expr(&call(var("head"), list(vec![bool(true)])), &named("Bool"));
+        assert_infers_expr(&call(var("tail"), list(vec![list(vec![])])), &list_type(list_type(tvar("a"))));
+    }
+
+    #[test]
+    fn infers_records_and_field_access() {
+        let r = record(vec![("name", bool(true)), ("age", int(3))]);
+        assert_infers_expr(&r, &record_type(vec![("age", named("Int")), ("name", named("Bool"))]));
+        assert_infers_expr(&field(r.clone(), "age"), &named("Int"));
+
+        let err = infer_full(&field(r, "email")).unwrap_err();
+        assert!(matches!(err, TypeError::MissingField { field, .. } if field == "email"));
+    }
+
+    #[test]
+    fn field_access_works_on_any_record_with_the_field() {
+        let get_name = func("r", field(var("r"), "name"));
+        assert_infers_expr(&get_name, &fun_type(open_record_type(vec![("name", tvar("a"))], "r"), tvar("a")));
+
+        let person = record(vec![("name", bool(true)), ("age", int(3))]);
+        let pet = record(vec![("name", int(1)), ("legs", int(4))]);
+        let e = let_("get_name", get_name, tuple(vec![
+            call(var("get_name"), person),
+            call(var("get_name"), pet.clone()),
+        ]));
+        assert_infers_expr(&e, &tuple_type(vec![named("Bool"), named("Int")]));
+
+        // Accessing two fields requires both of them
+        let both = func("r", tuple(vec![field(var("r"), "name"), field(var("r"), "age")]));
+        assert!(infer_full(&call(both, pet)).is_err());
+    }
+
+    #[test]
+    fn infers_tuple_projections() {
+        assert_infers("(1, true).1", "Bool");
+        assert_infers("((1, true), 2).0.1", "Bool");
+        assert_infers("let p = (1, true) in if p.1 then p.0 else 2", "Int");
+
+        let e = parse("(1, true).2").unwrap();
+        assert_type_error(&e, |err| matches!(err, TypeError::TupleIndexOutOfRange { index: 2, .. }));
+        let e = parse("not.0").unwrap();
+        assert_type_error(&e, |err| matches!(err, TypeError::NotATuple { index: 0, .. }));
+    }
+
+    #[test]
+    fn projections_of_parameters_leave_the_size_of_the_tuple_open() {
+        // A parameter can be a tuple of any size that has the element
+        let (type_, _subs) = infer_full(&parse("fun p -> p.1").unwrap()).unwrap();
+        assert!(alpha_equivalent(&type_, &fun_type(open_tuple_type(vec![(1, tvar("a"))], "r"), tvar("a"))));
+        let (type_, _subs) = infer_full(&parse("fun p -> p.0 + p.2").unwrap()).unwrap();
+        let open = open_tuple_type(vec![(0, named("Int")), (2, named("Int"))], "r");
+        assert!(alpha_equivalent(&type_, &fun_type(open, named("Int"))));
+
+        // Once the tuple is known the size is checked
+        assert_infers("(\\p -> p.1) (1, true)", "Bool");
+        assert_infers("let snd = fun p -> p.1 in (snd (1, true), snd (1, 2, 3))", "(Bool, Int)");
+        assert_infers("fun p -> if true then p else (1, p.1)", "(Int, a) -> (Int, a)");
+        let e = parse("(fun p -> p.2) (1, true)").unwrap();
+        assert_type_error(&e, |err| matches!(err, TypeError::TupleIndexOutOfRange { index: 2, .. }));
+        let e = parse("fun p -> p.0 + p").unwrap();
+        assert_type_error(&e, |err| matches!(err, TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn record_errors() {
+        let err = infer_full(&record(vec![("x", int(1)), ("x", int(2))])).unwrap_err();
+        assert_eq!(err, TypeError::DuplicateField("x".to_string()));
+        let err = infer_full(&field(int(1), "x")).unwrap_err();
+        assert_eq!(err, TypeError::NotARecord { field: "x".to_string(), found: named("Int") });
+    }
+
+    fn infer_with_maybe(e: &Box<Expression>) -> Result<Box<Type>, TypeError> {
+        let mut ctx = Context::new(Env::intial()).with_data_type(maybe_data_type());
+        infer(&mut ctx, e).map(|(type_, _subs)| type_)
+    }
+
+    #[test]
+    fn infers_constructors_with_parametric_types() {
+        let maybe = |arg| app_type("Maybe", vec![arg]);
+        assert_eq!(infer_with_maybe(&constructor("Just", vec![int(1)])).unwrap(), maybe(named("Int")));
+        let nothing = infer_with_maybe(&constructor("Nothing", vec![])).unwrap();
+        assert!(alpha_equivalent(&nothing, &maybe(tvar("a"))));
+
+        // Without its value Just is a function
+        let just = infer_with_maybe(&constructor("Just", vec![])).unwrap();
+        assert!(alpha_equivalent(&just, &fun_type(tvar("a"), maybe(tvar("a")))));
+
+        let e = list(vec![constructor("Nothing", vec![]), constructor("Just", vec![bool(true)])]);
+        assert_eq!(infer_with_maybe(&e).unwrap(), list_type(maybe(named("Bool"))));
+    }
+
+    #[test]
+    fn constructor_errors() {
+        let err = infer_with_maybe(&constructor("Just", vec![int(1), int(2)])).unwrap_err();
+        assert_eq!(err, TypeError::ArityMismatch { name: "Just".to_string(), expected: 1, found: 2 });
+        let err = infer_with_maybe(&constructor("Left", vec![int(1)])).unwrap_err();
+        assert_eq!(err, TypeError::UnknownConstructor("Left".to_string()));
+    }
+
+    #[test]
+    fn infers_matches_with_arm_local_bindings() {
+        // \m -> match m with | Nothing -> 0 | Just x -> x
+        let e = func("m", match_(var("m"), vec![
+            (pcon("Nothing", vec![]), int(0)),
+            (pcon("Just", vec![pvar("x")]), var("x")),
+        ]));
+        let maybe_int = app_type("Maybe", vec![named("Int")]);
+        assert_eq!(infer_with_maybe(&e).unwrap(), fun_type(maybe_int, named("Int")));
+
+        // \p -> match p with | (true, y) -> y | (_, _) -> 1
+        let e = func("p", match_(var("p"), vec![
+            (ptuple(vec![pbool(true), pvar("y")]), var("y")),
+            (ptuple(vec![pvar("_"), pvar("_")]), int(1)),
+        ]));
+        assert_infers_expr(&e, &fun_type(tuple_type(vec![named("Bool"), named("Int")]), named("Int")));
+
+        // The variable of an arm is not visible in the next one
+        let e = match_(int(1), vec![(pvar("x"), var("x")), (pint(2), var("x"))]);
+        assert_eq!(infer_full(&e).unwrap_err(), TypeError::UnboundVariable("x".to_string()));
+    }
+
+    #[test]
+    fn match_errors() {
+        let arms_disagree = match_(int(1), vec![(pint(1), int(1)), (pvar("_"), bool(true))]);
+        assert!(matches!(infer_full(&arms_disagree).unwrap_err(), TypeError::Mismatch { .. }));
+        let wrong_pattern = match_(int(1), vec![(pbool(true), int(1))]);
+        assert!(matches!(infer_full(&wrong_pattern).unwrap_err(), TypeError::Mismatch { .. }));
+        let twice = match_(tuple(vec![int(1), int(2)]), vec![(ptuple(vec![pvar("x"), pvar("x")]), int(1))]);
+        assert_eq!(infer_full(&twice).unwrap_err(), TypeError::DuplicatePatternVariable("x".to_string()));
+        let arity = match_(int(1), vec![(pcon("Just", vec![]), int(1))]);
+        assert!(matches!(infer_with_maybe(&arity).unwrap_err(), TypeError::ArityMismatch { .. }));
+    }
+
+    #[test]
+    fn try_recovers_from_the_error_of_a_result() {
+        let infer_with_result = |src: &str| {
+            let mut ctx = Context::new(Env::intial()).with_result();
+            infer(&mut ctx, &Box::new(parse(src).unwrap())).map(|(type_, _subs)| type_)
+        };
+        assert_eq!(infer_with_result("try Err true catch e -> if e then 1 else 2").unwrap(), named("Int"));
+        let recovers = infer_with_result("fun r -> try r catch e -> 0").unwrap();
+        assert!(alpha_equivalent(&recovers, &parse_type("Result Int b -> Int").unwrap()));
+
+        // The handler has to make a value of the type the Result holds
+        assert!(matches!(infer_with_result("try Ok 1 catch e -> true").unwrap_err(), TypeError::Mismatch { .. }));
+        assert!(matches!(infer_with_result("try 1 catch e -> 0").unwrap_err(), TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn infers_operators() {
+        assert_infers("1 + 2 * 3", "Int");
+        assert_infers("fun x -> x < 3", "Int -> Bool");
+        // Equality works for any type, but both sides have to agree
+        assert_infers("fun x y -> x == y", "a -> a -> Bool");
+        assert_infers("true == false", "Bool");
+    }
+
+    #[test]
+    fn operator_errors() {
+        let err = infer_full(&binop(BinOp::Sub, int(1), bool(true))).unwrap_err();
+        assert_eq!(err, TypeError::Mismatch { expected: named("Int"), found: named("Bool") });
+        assert_type_error(&binop(BinOp::Eq, int(1), bool(true)), |err| matches!(err, TypeError::Mismatch { .. }));
+        assert_type_error(&binop(BinOp::Gt, bool(true), int(1)), |err| err.to_string().contains("expected: Int"));
+    }
+
+    #[test]
+    fn annotations_narrow_the_type() {
+        let id = || func("x", var("x"));
+        assert_infers_expr(&annot(id(), fun_type(named("Int"), named("Int"))), &fun_type(named("Int"), named("Int")));
+        assert_infers_expr(&annot(id(), fun_type(tvar("a"), tvar("a"))), &fun_type(tvar("a"), tvar("a")));
+
+        // Variables of separate annotations are independent
+        let pair = tuple(vec![annot(int(1), tvar("a")), annot(bool(true), tvar("a"))]);
+        assert_infers_expr(&pair, &tuple_type(vec![named("Int"), named("Bool")]));
+    }
+
+    #[test]
+    fn annotation_mismatch_reports_both_types() {
+        let e = annot(func("x", binop(BinOp::Add, var("x"), int(1))), fun_type(named("Int"), named("Bool")));
+        let err = infer_full(&e).unwrap_err();
+        assert_eq!(
+            err,
+            TypeError::AnnotationMismatch {
+                annotated: fun_type(named("Int"), named("Bool")),
+                inferred: fun_type(named("Int"), named("Int")),
+            }
+        );
+        assert_eq!(err.to_string(), "Expression is annotated as Int -> Bool, but its type is Int -> Int");
+    }
+
+    #[test]
+    fn multi_parameter_functions_are_curried() {
+        assert_infers("\\f g x -> f (g x)", "(b -> c) -> (a -> b) -> a -> c");
+        assert_infers("(\\x y -> y) 1", "a -> a");
+    }
+
+    #[test]
+    fn infers_calls() {
+        assert_infers_expr(&call(func("a", var("a")), bool(true)), &named("Bool"));
+        assert_infers_expr(&call(var("not"), bool(true)), &named("Bool"));
+        assert_type_error(&call(var("not"), int(1)), |err| matches!(err, TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn infers_if() {
+        assert_infers("fun c x -> if c then x else 1", "Bool -> Int -> Int");
+    }
+
+    #[test]
+    fn if_needs_a_boolean_condition() {
+        let err = infer_full(&if_(int(1), int(2), int(3))).unwrap_err();
+        assert_eq!(err, TypeError::Mismatch { expected: named("Bool"), found: named("Int") });
+    }
+
+    #[test]
+    fn if_branches_have_to_agree() {
+        assert_type_error(&if_(bool(true), int(1), bool(false)), |err| matches!(err, TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn infers_assert() {
+        let e = Expression::EAssert { cond: bool(true), body: int(1) };
+        assert_infers_expr(&e, &named("Int"));
+        let e = Expression::EAssert { cond: int(1), body: int(1) };
+        assert!(infer_full(&e).is_err());
+    }
+
+    #[test]
+    fn let_bindings_extend_the_context() {
+        let e = let_("f", func("x", var("x")), call(var("f"), int(1)));
+        assert_infers_expr(&e, &named("Int"));
+    }
+
+    #[test]
+    fn let_bindings_are_polymorphic() {
+        assert_infers("let id = fun x -> x in if id true then id 1 else 2", "Int");
+    }
+
+    #[test]
+    fn let_patterns_take_tuples_apart() {
+        let e = let_pattern(
+            ptuple(vec![pvar("a"), pvar("b")]),
+            tuple(vec![int(1), bool(true)]),
+            if_(var("b"), var("a"), int(0)),
+        );
+        assert_infers_expr(&e, &named("Int"));
+
+        // The variables are generalized like the name of a let
+        let e = let_pattern(
+            ptuple(vec![pvar("id"), pvar("n")]),
+            tuple(vec![func("x", var("x")), int(1)]),
+            tuple(vec![call(var("id"), bool(true)), call(var("id"), var("n"))]),
+        );
+        assert_infers_expr(&e, &tuple_type(vec![named("Bool"), named("Int")]));
+
+        let e = let_pattern(ptuple(vec![pvar("a"), pvar("b")]), tuple(vec![int(1)]), var("a"));
+        assert!(infer_full(&e).is_err());
+    }
+
+    #[test]
+    fn extern_values_have_the_scheme_they_are_given() {
+        // The scheme is trusted as is, a let generalizes it back to the same scheme
+        let use_both = if_(
+            call(call(var("const"), bool(true)), int(1)),
+            call(call(var("const"), int(1)), bool(true)),
+            int(2),
+        );
+        let e = let_("const", extern_("a -> b -> a"), use_both);
+        assert_infers_expr(&e, &named("Int"));
+
+        let e = let_("parse", extern_("Int -> Bool"), binop(BinOp::Add, call(var("parse"), int(1)), int(2)));
+        assert!(infer_full(&e).is_err());
+    }
+
+    #[test]
+    fn type_applications_instantiate_the_scheme() {
+        assert_infers("let id = fun x -> x in id @Int", "Int -> Int");
+        assert_infers("let id = fun x -> x in id @(List a) [1]", "List Int");
+        assert_infers("(fun x -> x) @Bool", "Bool -> Bool");
+
+        // Every application fixes the next quantified variable
+        let mut ctx = Context::new(Env::intial().with_combinators());
+        let (type_, _subs) = infer(&mut ctx, &Box::new(parse("const @Int @Bool").unwrap())).unwrap();
+        assert_eq!(type_, fun_type(named("Int"), fun_type(named("Bool"), named("Int"))));
+
+        let not_polymorphic = |err: &TypeError| matches!(err, TypeError::NotPolymorphic(_));
+        assert_type_error(&parse("1 @Int").unwrap(), not_polymorphic);
+        assert_type_error(&parse("fun x -> x @Int").unwrap(), not_polymorphic);
+        assert_type_error(&parse("let id = fun x -> x in id @Int @Bool").unwrap(), not_polymorphic);
+        assert_type_error(&parse("let id = fun x -> x in id @List").unwrap(), |err| {
+            *err == TypeError::KindMismatch { name: "List".to_string(), expected: 1, found: 0 }
+        });
+    }
+
+    #[test]
+    fn lambda_parameters_are_monomorphic() {
+        let e = func("id", if_(call(var("id"), bool(true)), call(var("id"), int(1)), int(2)));
+        assert!(infer_full(&e).is_err());
+    }
+
+    #[test]
+    fn infers_recursive_bindings() {
+        assert_infers("let rec loop = fun n -> if true then n else loop n in loop 1", "Int");
+    }
+
+    #[test]
+    fn infers_mutually_recursive_groups() {
+        let even_odd = "let rec isEven = fun n -> if n == 0 then true else isOdd (n - 1) \
+                        and isOdd = fun n -> if n == 0 then false else isEven (n - 1) in";
+        assert_infers(&format!("{} isEven", even_odd), "Int -> Bool");
+        assert_infers(&format!("{} isOdd 7", even_odd), "Bool");
+
+        // The uses inside of the group have to agree with each other
+        let e = crate::parser::parse("let rec f = fun x -> g x and g = fun y -> y + 1 in f true").unwrap();
+        assert_type_error(&e, |err| matches!(err, TypeError::Mismatch { .. }));
+
+        // After the group the names are generalized
+        let group = "let rec first = fun x -> second x and second = fun y -> y in (first 1, first true)";
+        assert_infers(group, "(Int, Bool)");
+    }
+
+    #[test]
+    fn reports_unbound_variables() {
+        assert_eq!(infer_full(&var("nope")).unwrap_err(), TypeError::UnboundVariable("nope".to_string()));
+    }
+
+    #[test]
+    fn rejects_self_application() {
+        assert_type_error(&func("x", call(var("x"), var("x"))), |err| {
+            matches!(err, TypeError::OccursCheck { .. }) && err.to_string().contains("infinite type")
+        });
+    }
+
+    #[test]
+    fn deferred_occurs_check_still_rejects_infinite_types() {
+        let e = func("x", call(var("x"), var("x")));
+        let err = infer_with_deferred_occurs_check(&e).unwrap_err();
+        assert!(matches!(err, TypeError::OccursCheck { .. }), "{}", err);
+
+        // The cycle is found even when a later binding of the variable hides it in the substitution,
+        // here `x 1` binds the variable of x again after `x x` bound it to a type that contains it
+        let e = parse("fun x -> (x x, x 1)").unwrap();
+        let err = infer_with_deferred_occurs_check(&e).unwrap_err();
+        assert!(matches!(err, TypeError::OccursCheck { .. }), "{}", err);
+
+        // Without a cycle it infers what the occurs check does
+        for src in ["fun f -> fun x -> f (f x)", "let rec len = fun xs -> 1 + len (tail xs) in len", "fun p -> p.1.0"] {
+            let e = parse(src).unwrap();
+            let (type_, _subs) = infer_with_deferred_occurs_check(&e).unwrap();
+            assert!(alpha_equivalent(&type_, &infer_full(&e).unwrap().0), "{}", src);
+        }
+    }
+
+    #[test]
+    fn free_variables_become_implicit_parameters() {
+        let mut ctx = Context::new(Env::intial());
+        let e = binop(BinOp::Add, var("x"), var("y"));
+        let (type_, params) = infer_with_implicit_params(&mut ctx, &e).unwrap();
+        assert_eq!(type_, fun_type(named("Int"), fun_type(named("Int"), named("Int"))));
+        assert_eq!(params, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn reports_required_signatures() {
+        let signatures = required_signatures(&call(var("f"), int(1))).unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].0, "f");
+        assert!(alpha_equivalent(&signatures[0].1, &fun_type(named("Int"), tvar("a"))));
+    }
+
+    #[test]
+    fn compares_expressions_by_type() {
+        assert!(same_type(&func("x", var("x")), &func("y", var("y"))).unwrap());
+        assert!(!same_type(&func("x", var("x")), &func("x", int(1))).unwrap());
+    }
+
+    #[test]
+    fn detects_monomorphic_expressions() {
+        assert!(is_monomorphic(&int(1)).unwrap());
+        assert!(!is_monomorphic(&func("x", var("x"))).unwrap());
+    }
+
+    #[test]
+    fn first_order_signatures_reject_function_arguments() {
+        assert!(first_order_signature(&func("x", call(var("not"), var("x")))).is_ok());
+        let higher_order = first_order_signature(&func("f", call(var("f"), int(1))));
+        assert!(matches!(higher_order, Err(ExportError::HigherOrder(_))));
+    }
+
+    #[test]
+    fn infer_full_exposes_the_substitution() {
+        let (_type, subs) = infer_full(&call(func("a", var("a")), bool(true))).unwrap();
+        assert_eq!(subs.get("T0"), Some(&named("Bool")));
+    }
+
+    #[test]
+    fn infers_source_text() {
+        assert_eq!(infer_str("if true then 1 else 2").unwrap(), named("Int"));
+        assert!(matches!(infer_str("1 +"), Err(TypeError::Parse(_))));
+        assert!(matches!(infer_str("y"), Err(TypeError::UnboundVariable(_))));
+    }
+
+    #[test]
+    fn infers_every_expression_of_a_file() {
+        let types = infer_file("fun x -> x + 1;;\nif y then 1 else 2;;\n");
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0], Ok(fun_type(named("Int"), named("Int"))));
+        assert_eq!(types[1], Err(TypeError::UnboundVariable("y".to_string())));
+    }
+
+    #[test]
+    fn infers_a_batch_of_independent_expressions() {
+        let exprs = [
+            parse("fun x -> x").unwrap(),
+            parse("const 1").unwrap(),
+            parse("let x = 1 in x").unwrap(),
+            // A binding of an earlier expression is not visible in a later one
+            parse("x").unwrap(),
+            parse("not 1").unwrap(),
+            parse("flip const true").unwrap(),
+        ];
+        let types = infer_batch(&exprs, &Env::intial().with_combinators());
+        assert_eq!(types.len(), exprs.len());
+        assert_eq!(types[0], Ok(fun_type(tvar("T0"), tvar("T0"))));
+        assert!(alpha_equivalent(types[1].as_ref().unwrap(), &fun_type(tvar("b"), named("Int"))));
+        assert_eq!(types[2], Ok(named("Int")));
+        assert_eq!(types[3], Err(TypeError::UnboundVariable("x".to_string())));
+        assert!(matches!(types[4], Err(TypeError::Mismatch { .. })));
+        assert!(alpha_equivalent(types[5].as_ref().unwrap(), &fun_type(tvar("a"), tvar("a"))));
+    }
+
+    #[test]
+    fn formats_the_type_with_its_substitution() {
+        let (type_, subs) = infer_full(&call(func("a", var("a")), bool(true))).unwrap();
+        assert_eq!(format_result(&type_, &subs), "Bool\n  T0 = Bool\n  T1 = Bool");
+    }
+}
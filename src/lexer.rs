@@ -0,0 +1,400 @@
+//! Splits source text into tokens for the parser
+
+use crate::error::ParseError;
+use std::fmt;
+
+/// The different kinds of tokens in the surface syntax
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Int(i32),
+    Ident(String),
+    Str(String),
+    Char(char),
+    // A `-- line comment`, without the dashes and the surrounding whitespace
+    Comment(String),
+    // Keywords
+    Let,
+    Rec,
+    And,
+    In,
+    If,
+    Then,
+    Else,
+    Fun,
+    Assert,
+    True,
+    False,
+    Data,
+    Match,
+    With,
+    Try,
+    Catch,
+    // Punctuation
+    Backslash,
+    Arrow,
+    LeftArrow,
+    Equals,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    Bar,
+    Colon,
+    At,
+    // Operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    EqualsEquals,
+    Less,
+    Greater,
+    AmpAmp,
+    BarBar,
+    Pipe,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Int(value) => write!(f, "{}", value),
+            TokenKind::Ident(name) => write!(f, "{}", name),
+            TokenKind::Str(text) => write!(f, "{:?}", text),
+            TokenKind::Char(c) => write!(f, "{:?}", c),
+            TokenKind::Comment(text) => write!(f, "-- {}", text),
+            TokenKind::Let => write!(f, "let"),
+            TokenKind::Rec => write!(f, "rec"),
+            TokenKind::And => write!(f, "and"),
+            TokenKind::In => write!(f, "in"),
+            TokenKind::If => write!(f, "if"),
+            TokenKind::Then => write!(f, "then"),
+            TokenKind::Else => write!(f, "else"),
+            TokenKind::Fun => write!(f, "fun"),
+            TokenKind::Assert => write!(f, "assert"),
+            TokenKind::True => write!(f, "true"),
+            TokenKind::False => write!(f, "false"),
+            TokenKind::Data => write!(f, "data"),
+            TokenKind::Match => write!(f, "match"),
+            TokenKind::With => write!(f, "with"),
+            TokenKind::Try => write!(f, "try"),
+            TokenKind::Catch => write!(f, "catch"),
+            TokenKind::Backslash => write!(f, "\\"),
+            TokenKind::Arrow => write!(f, "->"),
+            TokenKind::LeftArrow => write!(f, "<-"),
+            TokenKind::Equals => write!(f, "="),
+            TokenKind::LParen => write!(f, "("),
+            TokenKind::RParen => write!(f, ")"),
+            TokenKind::LBracket => write!(f, "["),
+            TokenKind::RBracket => write!(f, "]"),
+            TokenKind::LBrace => write!(f, "{{"),
+            TokenKind::RBrace => write!(f, "}}"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::Bar => write!(f, "|"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::At => write!(f, "@"),
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Minus => write!(f, "-"),
+            TokenKind::Star => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::EqualsEquals => write!(f, "=="),
+            TokenKind::Less => write!(f, "<"),
+            TokenKind::Greater => write!(f, ">"),
+            TokenKind::AmpAmp => write!(f, "&&"),
+            TokenKind::BarBar => write!(f, "||"),
+            TokenKind::Pipe => write!(f, "|>"),
+        }
+    }
+}
+
+/// A token together with the byte offset where it starts in the source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub offset: usize,
+}
+
+/// Turn the source into a list of tokens, skipping whitespace and comments
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ParseError> {
+    lex(src, false)
+}
+
+/// Like `tokenize`, but keep the comments as tokens so they can be printed again
+pub fn tokenize_with_comments(src: &str) -> Result<Vec<Token>, ParseError> {
+    lex(src, true)
+}
+
+fn lex(src: &str, keep_comments: bool) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        let kind = match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' => {
+                let mut end = offset;
+                while let Some(&(idx, d)) = chars.peek() {
+                    if !d.is_ascii_digit() {
+                        break;
+                    }
+                    end = idx + d.len_utf8();
+                    chars.next();
+                }
+                let value = src[offset..end]
+                    .parse()
+                    .map_err(|_| ParseError::InvalidInt { text: src[offset..end].to_string(), offset })?;
+                TokenKind::Int(value)
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let mut end = offset;
+                while let Some(&(idx, d)) = chars.peek() {
+                    if !(d.is_alphanumeric() || d == '_' || d == '\'') {
+                        break;
+                    }
+                    end = idx + d.len_utf8();
+                    chars.next();
+                }
+                keyword_or_ident(&src[offset..end])
+            }
+            '"' => {
+                chars.next();
+                TokenKind::Str(literal(src, &mut chars, '"', offset)?)
+            }
+            '\'' => {
+                chars.next();
+                let text = literal(src, &mut chars, '\'', offset)?;
+                let mut text_chars = text.chars();
+                match (text_chars.next(), text_chars.next()) {
+                    (Some(c), None) => TokenKind::Char(c),
+                    _ => return Err(ParseError::InvalidChar { text: format!("'{}'", text), offset }),
+                }
+            }
+            '-' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, '>')) => {
+                        chars.next();
+                        TokenKind::Arrow
+                    }
+                    // A comment runs up to the end of the line
+                    Some(&(start, '-')) => {
+                        let mut end = start;
+                        while let Some(&(idx, d)) = chars.peek() {
+                            if d == '\n' {
+                                break;
+                            }
+                            end = idx + d.len_utf8();
+                            chars.next();
+                        }
+                        if !keep_comments {
+                            continue;
+                        }
+                        TokenKind::Comment(src[start + 1..end].trim().to_string())
+                    }
+                    _ => TokenKind::Minus,
+                }
+            }
+            '=' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, '=')) => {
+                        chars.next();
+                        TokenKind::EqualsEquals
+                    }
+                    _ => TokenKind::Equals,
+                }
+            }
+            '<' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, '-')) => {
+                        chars.next();
+                        TokenKind::LeftArrow
+                    }
+                    _ => TokenKind::Less,
+                }
+            }
+            '|' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, '|')) => {
+                        chars.next();
+                        TokenKind::BarBar
+                    }
+                    Some(&(_, '>')) => {
+                        chars.next();
+                        TokenKind::Pipe
+                    }
+                    _ => TokenKind::Bar,
+                }
+            }
+            // There is no single `&`
+            '&' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, '&')) => {
+                        chars.next();
+                        TokenKind::AmpAmp
+                    }
+                    _ => return Err(ParseError::UnexpectedChar { ch: c, offset }),
+                }
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '\\' => TokenKind::Backslash,
+                    '(' => TokenKind::LParen,
+                    ')' => TokenKind::RParen,
+                    '[' => TokenKind::LBracket,
+                    ']' => TokenKind::RBracket,
+                    '{' => TokenKind::LBrace,
+                    '}' => TokenKind::RBrace,
+                    ',' => TokenKind::Comma,
+                    '.' => TokenKind::Dot,
+                    ':' => TokenKind::Colon,
+                    '@' => TokenKind::At,
+                    '+' => TokenKind::Plus,
+                    '*' => TokenKind::Star,
+                    '/' => TokenKind::Slash,
+                    '>' => TokenKind::Greater,
+                    _ => return Err(ParseError::UnexpectedChar { ch: c, offset }),
+                }
+            }
+        };
+        tokens.push(Token { kind, offset });
+    }
+
+    Ok(tokens)
+}
+
+/// Read the rest of a string or character literal after its opening quote, up to and including the
+/// closing quote. A backslash escapes the quotes, itself, and makes `\n` and `\t` a newline and a tab
+fn literal(
+    src: &str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    quote: char,
+    offset: usize,
+) -> Result<String, ParseError> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(ParseError::UnterminatedLiteral { offset }),
+            Some((_, c)) if c == quote => return Ok(text),
+            Some((idx, '\\')) => match chars.next() {
+                Some((_, 'n')) => text.push('\n'),
+                Some((_, 't')) => text.push('\t'),
+                Some((_, c)) if c == '\\' || c == '"' || c == '\'' => text.push(c),
+                Some((end, c)) => {
+                    let escape = &src[idx..end + c.len_utf8()];
+                    return Err(ParseError::InvalidChar { text: escape.to_string(), offset: idx });
+                }
+                None => return Err(ParseError::UnterminatedLiteral { offset }),
+            },
+            Some((_, c)) => text.push(c),
+        }
+    }
+}
+
+fn keyword_or_ident(word: &str) -> TokenKind {
+    match word {
+        "let" => TokenKind::Let,
+        "rec" => TokenKind::Rec,
+        "and" => TokenKind::And,
+        "in" => TokenKind::In,
+        "if" => TokenKind::If,
+        "then" => TokenKind::Then,
+        "else" => TokenKind::Else,
+        "fun" => TokenKind::Fun,
+        "assert" => TokenKind::Assert,
+        "true" => TokenKind::True,
+        "false" => TokenKind::False,
+        "data" => TokenKind::Data,
+        "match" => TokenKind::Match,
+        "with" => TokenKind::With,
+        "try" => TokenKind::Try,
+        "catch" => TokenKind::Catch,
+        _ => TokenKind::Ident(word.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        tokenize(src).unwrap().into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn tokenizes_keywords_identifiers_and_punctuation() {
+        assert_eq!(
+            kinds("let f = \\x -> f' x in f 12"),
+            vec![
+                TokenKind::Let,
+                TokenKind::Ident("f".to_string()),
+                TokenKind::Equals,
+                TokenKind::Backslash,
+                TokenKind::Ident("x".to_string()),
+                TokenKind::Arrow,
+                TokenKind::Ident("f'".to_string()),
+                TokenKind::Ident("x".to_string()),
+                TokenKind::In,
+                TokenKind::Ident("f".to_string()),
+                TokenKind::Int(12),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_offsets() {
+        let tokens = tokenize("if  x").unwrap();
+        assert_eq!(tokens[1].offset, 4);
+    }
+
+    #[test]
+    fn tells_apart_operators_that_start_the_same() {
+        assert_eq!(
+            kinds("= == - ->"),
+            vec![TokenKind::Equals, TokenKind::EqualsEquals, TokenKind::Minus, TokenKind::Arrow]
+        );
+        assert_eq!(kinds("| || |> &&"), vec![TokenKind::Bar, TokenKind::BarBar, TokenKind::Pipe, TokenKind::AmpAmp]);
+        assert_eq!(kinds("< <- < -"), vec![TokenKind::Less, TokenKind::LeftArrow, TokenKind::Less, TokenKind::Minus]);
+        assert_eq!(tokenize("x & y").unwrap_err(), ParseError::UnexpectedChar { ch: '&', offset: 2 });
+    }
+
+    #[test]
+    fn comments_run_to_the_end_of_the_line() {
+        assert_eq!(kinds("x -- the rest -> is ignored\n- y"), kinds("x - y"));
+        let tokens = tokenize_with_comments("x --  a note \ny").unwrap();
+        assert_eq!(tokens[1].kind, TokenKind::Comment("a note".to_string()));
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[test]
+    fn reads_string_and_character_literals() {
+        assert_eq!(
+            kinds(r#"f' "a \"b\"\n" 'c' '\''"#),
+            vec![
+                TokenKind::Ident("f'".to_string()),
+                TokenKind::Str("a \"b\"\n".to_string()),
+                TokenKind::Char('c'),
+                TokenKind::Char('\''),
+            ]
+        );
+        assert_eq!(tokenize("x \"abc").unwrap_err(), ParseError::UnterminatedLiteral { offset: 2 });
+        assert_eq!(tokenize("'ab'").unwrap_err(), ParseError::InvalidChar { text: "'ab'".to_string(), offset: 0 });
+        assert_eq!(tokenize(r#""\q""#).unwrap_err(), ParseError::InvalidChar { text: "\\q".to_string(), offset: 1 });
+    }
+
+    #[test]
+    fn rejects_unknown_characters() {
+        assert_eq!(tokenize("x $").unwrap_err(), ParseError::UnexpectedChar { ch: '$', offset: 2 });
+    }
+}
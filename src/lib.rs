@@ -0,0 +1,20 @@
+//! Type inference for a small lambda calculus with let-polymorphism
+#![allow(clippy::borrowed_box, clippy::vec_box, clippy::enum_variant_names)]
+
+pub mod ast;
+pub mod desugar;
+pub mod env;
+pub mod error;
+pub mod exhaustive;
+pub mod format;
+pub mod infer;
+pub mod lexer;
+pub mod parser;
+pub mod position;
+pub mod prelude;
+pub mod repl;
+pub mod types;
+pub mod unify;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,32 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use typing_test::repl::Session;
+
+fn main() {
+    match env::args().nth(1).as_deref() {
+        Some("repl") => repl(),
+        _ => println!("Usage: typing_test repl"),
+    }
+}
+
+/// Read lines from stdin and print the inferred type of each, until the input ends
+fn repl() {
+    let mut session = Session::new();
+    let stdin = io::stdin();
+    prompt();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Could not read from stdin");
+        if !line.trim().is_empty() {
+            match session.eval(&line) {
+                Ok(output) => println!("{}", output),
+                Err(err) => println!("{}", err),
+            }
+        }
+        prompt();
+    }
+}
+
+fn prompt() {
+    print!("> ");
+    io::stdout().flush().expect("Could not write to stdout");
+}
@@ -0,0 +1,970 @@
+//! Recursive descent parser from source text to an expression
+//!
+//! The grammar, from loosest to tightest binding:
+//!
+//! ```text
+//! stmt   := 'let' ['rec'] ident '=' expr
+//!         | 'data' Ident ident* '=' Ident atype* ('|' Ident atype*)*
+//!         | expr
+//! expr   := 'let' ['rec'] ident '=' expr 'in' expr
+//!         | 'let' '(' pattern (',' pattern)* ')' '=' expr 'in' expr
+//!         | 'if' expr 'then' expr 'else' expr
+//!         | 'assert' expr 'in' expr
+//!         | ('\' | 'fun') ident+ '->' expr
+//!         | 'match' expr 'with' ['|'] arm ('|' arm)*
+//!         | 'try' expr 'catch' ident '->' expr
+//!         | pipe
+//! pipe   := annot ('|>' annot)*
+//! annot  := or [':' type]
+//! or     := and ('||' and)*
+//! and    := cmp ('&&' cmp)*
+//! cmp    := sum [('==' | '<' | '>') sum]
+//! sum    := term (('+' | '-') term)*
+//! term   := app (('*' | '/') app)*
+//! app    := Ident access* | access (access | '@' atype)*
+//! access := atom ('.' (ident | int))*
+//! atom   := int | string | char | 'true' | 'false' | ident
+//!         | '(' ')' | '(' expr (',' expr)* ')'
+//!         | '[' ']' | '[' expr (',' expr)* ']' | '[' expr '|' qual (',' qual)* ']'
+//!         | '{' '}' | '{' ident '=' expr (',' ident '=' expr)* '}'
+//!
+//! qual   := ident '<-' expr | expr
+//! arm    := pattern '->' expr
+//! pattern := Ident apattern* | apattern
+//! apattern := ident | int | 'true' | 'false' | Ident | '(' ')' | '(' pattern (',' pattern)* ')'
+//!
+//! type   := tapp ['->' type]
+//! tapp   := Ident atype* | atype
+//! atype  := ident | Ident | '(' ')' | '(' type (',' type)* ')'
+//! ```
+//!
+//! Names that start with an uppercase letter (Ident) are constructors in expressions
+//! and type names in types, the others (ident) are variables and type variables.
+//! A `--` starts a comment that runs to the end of the line.
+
+use crate::ast::{BinOp, Expression, Pattern};
+use crate::desugar::{desugar_comprehension, Qualifier};
+use crate::error::ParseError;
+use crate::lexer::{tokenize, tokenize_with_comments, Token, TokenKind};
+use crate::types::{Constructor, DataType, Type};
+
+/// Parse a complete expression, the whole source must be consumed
+pub fn parse(src: &str) -> Result<Expression, ParseError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser::new(tokens);
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(*expr),
+        Some(token) => Err(unexpected(token, "end of input")),
+    }
+}
+
+/// Parse a complete expression and keep its comments, each in front of the expression that follows it.
+/// Comments after the last expression go in front of the whole expression
+pub fn parse_with_comments(src: &str) -> Result<Expression, ParseError> {
+    let tokens = tokenize_with_comments(src)?;
+    let mut parser = Parser::new(tokens);
+    let expr = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(unexpected(token, "end of input"));
+    }
+    let comments = parser.take_comments();
+    if comments.is_empty() {
+        return Ok(*expr);
+    }
+    Ok(Expression::EComment { comments, expr })
+}
+
+/// Parse a complete type like `(a -> b) -> List a -> List b`
+pub fn parse_type(src: &str) -> Result<Box<Type>, ParseError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser::new(tokens);
+    let type_ = parser.type_()?;
+    match parser.peek() {
+        None => Ok(type_),
+        Some(token) => Err(unexpected(token, "end of input")),
+    }
+}
+
+/// A single line of input for an interactive session
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    // A top level binding without a body, it stays in scope for the following statements
+    Let {
+        name: String,
+        recursive: bool,
+        value: Expression,
+    },
+    // A declaration of a sum type, its constructors can be used in the following statements
+    Data(DataType),
+    // An expression to infer the type of
+    Expr(Expression),
+}
+
+/// Parse a statement, a `let` without an `in` is a binding and anything else is an expression
+pub fn parse_statement(src: &str) -> Result<Statement, ParseError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser::new(tokens);
+    if parser.peek_kind() == Some(&TokenKind::Data) {
+        let data_type = parser.data_type()?;
+        return match parser.peek() {
+            None => Ok(Statement::Data(data_type)),
+            Some(token) => Err(unexpected(token, "end of input")),
+        };
+    }
+    let expr = if parser.peek_kind() == Some(&TokenKind::Let) && !parser.at_let_pattern() {
+        let (name, recursive, value) = parser.let_binding()?;
+        if parser.peek().is_none() {
+            return Ok(Statement::Let { name, recursive, value: *value });
+        }
+        parser.let_body(name, recursive, value)?
+    } else {
+        parser.expr()?
+    };
+    match parser.peek() {
+        None => Ok(Statement::Expr(*expr)),
+        Some(token) => Err(unexpected(token, "end of input")),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // The comments in front of every token, and after the last one
+    comments: Vec<Vec<String>>,
+    // The tokens before this one have given their comments to an expression
+    claimed: usize,
+}
+
+impl Parser {
+    fn new(all_tokens: Vec<Token>) -> Parser {
+        let mut tokens = Vec::new();
+        let mut comments = vec![Vec::new()];
+        for token in all_tokens {
+            match token.kind {
+                TokenKind::Comment(text) => comments.last_mut().unwrap().push(text),
+                _ => {
+                    tokens.push(token);
+                    comments.push(Vec::new());
+                }
+            }
+        }
+        Parser { tokens, pos: 0, comments, claimed: 0 }
+    }
+
+    /// The comments that no expression has taken yet, up to the current token
+    fn take_comments(&mut self) -> Vec<String> {
+        let end = (self.pos + 1).min(self.comments.len());
+        let mut taken = Vec::new();
+        for comments in self.comments.iter_mut().take(end).skip(self.claimed) {
+            taken.append(comments);
+        }
+        self.claimed = self.claimed.max(end);
+        taken
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_kind(&self) -> Option<&TokenKind> {
+        self.peek().map(|token| &token.kind)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| ParseError::UnexpectedEnd { expected: expected.to_string() })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), ParseError> {
+        let expected = format!("'{}'", kind);
+        let token = self.next(&expected)?;
+        if token.kind == kind {
+            Ok(())
+        } else {
+            Err(unexpected(&token, &expected))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        let token = self.next("an identifier")?;
+        match token.kind {
+            TokenKind::Ident(name) => Ok(name),
+            _ => Err(unexpected(&token, "an identifier")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Box<Expression>, ParseError> {
+        // The comments in front of the expression stay with it
+        let comments = self.take_comments();
+        let expr = self.bare_expr()?;
+        if comments.is_empty() {
+            return Ok(expr);
+        }
+        Ok(Box::new(Expression::EComment { comments, expr }))
+    }
+
+    fn bare_expr(&mut self) -> Result<Box<Expression>, ParseError> {
+        match self.peek_kind() {
+            Some(TokenKind::Let) if self.at_let_pattern() => {
+                self.pos += 1;
+                let pattern = self.apattern()?;
+                self.expect(TokenKind::Equals)?;
+                let value = self.expr()?;
+                self.expect(TokenKind::In)?;
+                let body = self.expr()?;
+                Ok(Box::new(Expression::ELetPattern { pattern, value, body }))
+            }
+            Some(TokenKind::Let) => {
+                let (name, recursive, value) = self.let_binding()?;
+                self.let_body(name, recursive, value)
+            }
+            Some(TokenKind::If) => {
+                self.pos += 1;
+                let cond = self.expr()?;
+                self.expect(TokenKind::Then)?;
+                let true_b = self.expr()?;
+                self.expect(TokenKind::Else)?;
+                let false_b = self.expr()?;
+                Ok(Box::new(Expression::EIf { cond, true_b, false_b }))
+            }
+            Some(TokenKind::Assert) => {
+                self.pos += 1;
+                let cond = self.expr()?;
+                self.expect(TokenKind::In)?;
+                let body = self.expr()?;
+                Ok(Box::new(Expression::EAssert { cond, body }))
+            }
+            Some(TokenKind::Backslash) | Some(TokenKind::Fun) => {
+                self.pos += 1;
+                let mut params = vec![self.ident()?];
+                while let Some(TokenKind::Ident(_)) = self.peek_kind() {
+                    params.push(self.ident()?);
+                }
+                self.expect(TokenKind::Arrow)?;
+                let body = self.expr()?;
+                if params.len() == 1 {
+                    Ok(Box::new(Expression::EFunc { param: params.remove(0), body }))
+                } else {
+                    Ok(Box::new(Expression::EFuncN { params, body }))
+                }
+            }
+            Some(TokenKind::Try) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(TokenKind::Catch)?;
+                let error = self.ident()?;
+                self.expect(TokenKind::Arrow)?;
+                let handler = self.expr()?;
+                Ok(Box::new(Expression::ETry { expr, error, handler }))
+            }
+            Some(TokenKind::Match) => {
+                self.pos += 1;
+                let scrutinee = self.expr()?;
+                self.expect(TokenKind::With)?;
+                // The bar before the first arm is optional
+                if self.peek_kind() == Some(&TokenKind::Bar) {
+                    self.pos += 1;
+                }
+                let mut arms = vec![self.arm()?];
+                while self.peek_kind() == Some(&TokenKind::Bar) {
+                    self.pos += 1;
+                    arms.push(self.arm()?);
+                }
+                Ok(Box::new(Expression::EMatch { scrutinee, arms }))
+            }
+            _ => self.pipe(),
+        }
+    }
+
+    /// `x |> f` is `f x`, it binds looser than anything but the keyword expressions and
+    /// chains to the left. Application binds tighter, so `x |> f y` is `f y x`
+    fn pipe(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut arg = self.annot()?;
+        while self.peek_kind() == Some(&TokenKind::Pipe) {
+            self.pos += 1;
+            let func = self.annot()?;
+            arg = Box::new(Expression::ECall { func, arg });
+        }
+        Ok(arg)
+    }
+
+    fn annot(&mut self) -> Result<Box<Expression>, ParseError> {
+        let expr = self.or()?;
+        if self.peek_kind() != Some(&TokenKind::Colon) {
+            return Ok(expr);
+        }
+        self.pos += 1;
+        let type_ = self.type_()?;
+        Ok(Box::new(Expression::EAnnot { expr, type_ }))
+    }
+
+    /// `&&` binds tighter than `||`, so `a || b && c` is `a || (b && c)`
+    fn or(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut lhs = self.and()?;
+        while self.peek_kind() == Some(&TokenKind::BarBar) {
+            self.pos += 1;
+            let rhs = self.and()?;
+            lhs = Box::new(Expression::EBinOp { op: BinOp::Or, lhs, rhs });
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut lhs = self.cmp()?;
+        while self.peek_kind() == Some(&TokenKind::AmpAmp) {
+            self.pos += 1;
+            let rhs = self.cmp()?;
+            lhs = Box::new(Expression::EBinOp { op: BinOp::And, lhs, rhs });
+        }
+        Ok(lhs)
+    }
+
+    /// Comparisons do not chain, `a < b < c` is an error
+    fn cmp(&mut self) -> Result<Box<Expression>, ParseError> {
+        let lhs = self.sum()?;
+        let op = match self.peek_kind() {
+            Some(TokenKind::EqualsEquals) => BinOp::Eq,
+            Some(TokenKind::Less) => BinOp::Lt,
+            Some(TokenKind::Greater) => BinOp::Gt,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = self.sum()?;
+        Ok(Box::new(Expression::EBinOp { op, lhs, rhs }))
+    }
+
+    fn sum(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Plus) => BinOp::Add,
+                Some(TokenKind::Minus) => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Box::new(Expression::EBinOp { op, lhs, rhs });
+        }
+    }
+
+    fn term(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut lhs = self.app()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Star) => BinOp::Mul,
+                Some(TokenKind::Slash) => BinOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.app()?;
+            lhs = Box::new(Expression::EBinOp { op, lhs, rhs });
+        }
+    }
+
+    fn arm(&mut self) -> Result<(Pattern, Box<Expression>), ParseError> {
+        let pattern = self.pattern()?;
+        self.expect(TokenKind::Arrow)?;
+        let body = self.expr()?;
+        Ok((pattern, body))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        // Like in expressions, a constructor takes the patterns that follow it directly
+        if let Some(TokenKind::Ident(name)) = self.peek_kind() {
+            if is_upper(name) {
+                let name = self.ident()?;
+                let mut args = Vec::new();
+                while self.at_apattern() {
+                    args.push(self.apattern()?);
+                }
+                return Ok(Pattern::PConstructor { name, args });
+            }
+        }
+        self.apattern()
+    }
+
+    fn at_apattern(&self) -> bool {
+        matches!(
+            self.peek_kind(),
+            Some(TokenKind::Int(_))
+                | Some(TokenKind::True)
+                | Some(TokenKind::False)
+                | Some(TokenKind::Ident(_))
+                | Some(TokenKind::LParen)
+        )
+    }
+
+    fn apattern(&mut self) -> Result<Pattern, ParseError> {
+        let token = self.next("a pattern")?;
+        match token.kind {
+            TokenKind::Int(value) => Ok(Pattern::PInt { value }),
+            TokenKind::True => Ok(Pattern::PBool { value: true }),
+            TokenKind::False => Ok(Pattern::PBool { value: false }),
+            TokenKind::Ident(name) if is_upper(&name) => Ok(Pattern::PConstructor { name, args: Vec::new() }),
+            TokenKind::Ident(name) => Ok(Pattern::PVar { name }),
+            TokenKind::LParen => {
+                let mut elements = Vec::new();
+                if self.peek_kind() != Some(&TokenKind::RParen) {
+                    elements.push(self.pattern()?);
+                    while self.peek_kind() == Some(&TokenKind::Comma) {
+                        self.pos += 1;
+                        elements.push(self.pattern()?);
+                    }
+                }
+                self.expect(TokenKind::RParen)?;
+                if elements.len() == 1 {
+                    Ok(elements.remove(0))
+                } else {
+                    Ok(Pattern::PTuple { elements })
+                }
+            }
+            _ => Err(unexpected(&token, "a pattern")),
+        }
+    }
+
+    /// A let that takes its value apart, `let (a, b) = value in body`, always has a body
+    fn at_let_pattern(&self) -> bool {
+        self.tokens.get(self.pos + 1).map(|token| &token.kind) == Some(&TokenKind::LParen)
+    }
+
+    /// Parse `let [rec] name = value`, up to but not including the `in`
+    fn let_binding(&mut self) -> Result<(String, bool, Box<Expression>), ParseError> {
+        self.expect(TokenKind::Let)?;
+        let recursive = self.peek_kind() == Some(&TokenKind::Rec);
+        if recursive {
+            self.pos += 1;
+        }
+        let name = self.ident()?;
+        self.expect(TokenKind::Equals)?;
+        let value = self.expr()?;
+        Ok((name, recursive, value))
+    }
+
+    /// Parse the `in body` part of a let and build the expression,
+    /// a let rec can be followed by `and name = value` for more bindings of the same group
+    fn let_body(&mut self, name: String, recursive: bool, value: Box<Expression>) -> Result<Box<Expression>, ParseError> {
+        if recursive && self.peek_kind() == Some(&TokenKind::And) {
+            let mut bindings = vec![(name, value)];
+            while self.peek_kind() == Some(&TokenKind::And) {
+                self.pos += 1;
+                let name = self.ident()?;
+                self.expect(TokenKind::Equals)?;
+                bindings.push((name, self.expr()?));
+            }
+            self.expect(TokenKind::In)?;
+            let body = self.expr()?;
+            return Ok(Box::new(Expression::ELetRecGroup { bindings, body }));
+        }
+        self.expect(TokenKind::In)?;
+        let body = self.expr()?;
+        Ok(Box::new(if recursive {
+            Expression::ELetRec { name, value, body }
+        } else {
+            Expression::ELet { name, value, body }
+        }))
+    }
+
+    fn app(&mut self) -> Result<Box<Expression>, ParseError> {
+        // A constructor takes the values that follow it directly
+        if let Some(TokenKind::Ident(name)) = self.peek_kind() {
+            if is_upper(name) {
+                let name = self.ident()?;
+                let mut args = Vec::new();
+                while self.at_atom() {
+                    args.push(self.access()?);
+                }
+                return Ok(Box::new(Expression::EConstructor { name, args }));
+            }
+        }
+
+        let mut func = self.access()?;
+        loop {
+            if self.peek_kind() == Some(&TokenKind::At) {
+                // A type argument like in `id @Int 1`
+                self.pos += 1;
+                let ty = self.atype()?;
+                func = Box::new(Expression::ETypeApp { expr: func, ty });
+            } else if self.at_atom() {
+                let arg = self.access()?;
+                func = Box::new(Expression::ECall { func, arg });
+            } else {
+                return Ok(func);
+            }
+        }
+    }
+
+    /// Check if the next token can start an atom, so an application continues
+    fn at_atom(&self) -> bool {
+        matches!(
+            self.peek_kind(),
+            Some(TokenKind::Int(_))
+                | Some(TokenKind::Str(_))
+                | Some(TokenKind::Char(_))
+                | Some(TokenKind::True)
+                | Some(TokenKind::False)
+                | Some(TokenKind::Ident(_))
+                | Some(TokenKind::LParen)
+                | Some(TokenKind::LBracket)
+                | Some(TokenKind::LBrace)
+        )
+    }
+
+    fn access(&mut self) -> Result<Box<Expression>, ParseError> {
+        let mut record = self.atom()?;
+        while self.peek_kind() == Some(&TokenKind::Dot) {
+            self.pos += 1;
+            // A number after the dot projects an element of a tuple
+            if let Some(&TokenKind::Int(index)) = self.peek_kind() {
+                self.pos += 1;
+                record = Box::new(Expression::ETupleProject { tuple: record, index: index as usize });
+                continue;
+            }
+            let field = self.ident()?;
+            record = Box::new(Expression::EFieldAccess { record, field });
+        }
+        Ok(record)
+    }
+
+    fn atom(&mut self) -> Result<Box<Expression>, ParseError> {
+        let token = self.next("an expression")?;
+        match token.kind {
+            TokenKind::Int(value) => Ok(Box::new(Expression::EInt { value })),
+            TokenKind::Str(value) => Ok(Box::new(Expression::EString { value })),
+            TokenKind::Char(value) => Ok(Box::new(Expression::EChar { value })),
+            TokenKind::True => Ok(Box::new(Expression::EBool { value: true })),
+            TokenKind::False => Ok(Box::new(Expression::EBool { value: false })),
+            TokenKind::Ident(name) if is_upper(&name) => Ok(Box::new(Expression::EConstructor { name, args: Vec::new() })),
+            TokenKind::Ident(name) => Ok(Box::new(Expression::EVar { name })),
+            TokenKind::LParen => {
+                let mut elements = self.comma_separated(TokenKind::RParen)?;
+                // A single expression in parentheses is just grouping, otherwise it is a tuple
+                if elements.len() == 1 {
+                    Ok(elements.remove(0))
+                } else {
+                    Ok(Box::new(Expression::ETuple { elements }))
+                }
+            }
+            TokenKind::LBracket => {
+                if self.peek_kind() == Some(&TokenKind::RBracket) {
+                    self.pos += 1;
+                    return Ok(Box::new(Expression::EList { elements: Vec::new() }));
+                }
+                let first = self.expr()?;
+                if self.peek_kind() == Some(&TokenKind::Bar) {
+                    // A comprehension like `[x + y | x <- xs, y <- ys, x < y]`
+                    self.pos += 1;
+                    let mut qualifiers = vec![self.qualifier()?];
+                    while self.peek_kind() == Some(&TokenKind::Comma) {
+                        self.pos += 1;
+                        qualifiers.push(self.qualifier()?);
+                    }
+                    self.expect(TokenKind::RBracket)?;
+                    return Ok(Box::new(desugar_comprehension(first, &qualifiers)));
+                }
+                let elements = self.separated_after(first, TokenKind::RBracket)?;
+                Ok(Box::new(Expression::EList { elements }))
+            }
+            TokenKind::LBrace => {
+                let mut fields = Vec::new();
+                if self.peek_kind() != Some(&TokenKind::RBrace) {
+                    fields.push(self.record_field()?);
+                    while self.peek_kind() == Some(&TokenKind::Comma) {
+                        self.pos += 1;
+                        fields.push(self.record_field()?);
+                    }
+                }
+                self.expect(TokenKind::RBrace)?;
+                Ok(Box::new(Expression::ERecord { fields }))
+            }
+            _ => Err(unexpected(&token, "an expression")),
+        }
+    }
+
+    /// Parse a generator `x <- xs` or a guard of a comprehension
+    fn qualifier(&mut self) -> Result<Qualifier, ParseError> {
+        let next = self.tokens.get(self.pos + 1).map(|token| &token.kind);
+        if let (Some(TokenKind::Ident(_)), Some(TokenKind::LeftArrow)) = (self.peek_kind(), next) {
+            let var = self.ident()?;
+            self.pos += 1;
+            let list = self.expr()?;
+            return Ok(Qualifier::Generator { var, list });
+        }
+        Ok(Qualifier::Guard(self.expr()?))
+    }
+
+    /// Parse a single `field = value` of a record literal
+    fn record_field(&mut self) -> Result<(String, Box<Expression>), ParseError> {
+        let field = self.ident()?;
+        self.expect(TokenKind::Equals)?;
+        let value = self.expr()?;
+        Ok((field, value))
+    }
+
+    /// Parse `data Name params = Con fields | ...`
+    fn data_type(&mut self) -> Result<DataType, ParseError> {
+        self.expect(TokenKind::Data)?;
+        let name = self.upper_ident("a type name")?;
+        let mut params = Vec::new();
+        while let Some(TokenKind::Ident(_)) = self.peek_kind() {
+            params.push(self.ident()?);
+        }
+        self.expect(TokenKind::Equals)?;
+
+        let mut constructors = vec![self.constructor()?];
+        while self.peek_kind() == Some(&TokenKind::Bar) {
+            self.pos += 1;
+            constructors.push(self.constructor()?);
+        }
+        Ok(DataType { name, params, constructors })
+    }
+
+    fn constructor(&mut self) -> Result<Constructor, ParseError> {
+        let name = self.upper_ident("a constructor")?;
+        let mut fields = Vec::new();
+        while self.at_atype() {
+            fields.push(self.atype()?);
+        }
+        Ok(Constructor { name, fields })
+    }
+
+    /// Parse an identifier that starts with an uppercase letter
+    fn upper_ident(&mut self, expected: &str) -> Result<String, ParseError> {
+        let token = self.next(expected)?;
+        match token.kind {
+            TokenKind::Ident(name) if is_upper(&name) => Ok(name),
+            _ => Err(unexpected(&token, expected)),
+        }
+    }
+
+    fn type_(&mut self) -> Result<Box<Type>, ParseError> {
+        let from = self.tapp()?;
+        if self.peek_kind() == Some(&TokenKind::Arrow) {
+            self.pos += 1;
+            // Arrows associate to the right
+            let to = self.type_()?;
+            return Ok(Box::new(Type::TFun { from, to }));
+        }
+        Ok(from)
+    }
+
+    fn tapp(&mut self) -> Result<Box<Type>, ParseError> {
+        if let Some(TokenKind::Ident(name)) = self.peek_kind() {
+            if is_upper(name) {
+                let name = self.ident()?;
+                let mut args = Vec::new();
+                while self.at_atype() {
+                    args.push(self.atype()?);
+                }
+                if args.is_empty() {
+                    return Ok(Box::new(Type::TNamed { name }));
+                }
+                return Ok(Box::new(Type::TApp { name, args }));
+            }
+        }
+        self.atype()
+    }
+
+    fn at_atype(&self) -> bool {
+        matches!(self.peek_kind(), Some(TokenKind::Ident(_)) | Some(TokenKind::LParen))
+    }
+
+    fn atype(&mut self) -> Result<Box<Type>, ParseError> {
+        let token = self.next("a type")?;
+        match token.kind {
+            TokenKind::Ident(name) if is_upper(&name) => Ok(Box::new(Type::TNamed { name })),
+            TokenKind::Ident(name) => Ok(Box::new(Type::TVar { name })),
+            TokenKind::LParen => {
+                let mut elements = Vec::new();
+                if self.peek_kind() != Some(&TokenKind::RParen) {
+                    elements.push(self.type_()?);
+                    while self.peek_kind() == Some(&TokenKind::Comma) {
+                        self.pos += 1;
+                        elements.push(self.type_()?);
+                    }
+                }
+                self.expect(TokenKind::RParen)?;
+                // A single type in parentheses is just grouping, like for expressions
+                if elements.len() == 1 {
+                    Ok(elements.remove(0))
+                } else {
+                    Ok(Box::new(Type::TTuple { elements }))
+                }
+            }
+            _ => Err(unexpected(&token, "a type")),
+        }
+    }
+
+    /// Parse zero or more expressions separated by commas, up to and including the closing token
+    fn comma_separated(&mut self, close: TokenKind) -> Result<Vec<Box<Expression>>, ParseError> {
+        if self.peek_kind() == Some(&close) {
+            self.pos += 1;
+            return Ok(Vec::new());
+        }
+        let first = self.expr()?;
+        self.separated_after(first, close)
+    }
+
+    /// The rest of a comma separated list of expressions of which the first is already parsed
+    fn separated_after(&mut self, first: Box<Expression>, close: TokenKind) -> Result<Vec<Box<Expression>>, ParseError> {
+        let mut elements = vec![first];
+        while self.peek_kind() == Some(&TokenKind::Comma) {
+            self.pos += 1;
+            elements.push(self.expr()?);
+        }
+        self.expect(close)?;
+        Ok(elements)
+    }
+}
+
+fn is_upper(name: &str) -> bool {
+    name.chars().next().is_some_and(char::is_uppercase)
+}
+
+fn unexpected(token: &Token, expected: &str) -> ParseError {
+    ParseError::UnexpectedToken {
+        found: token.kind.to_string(),
+        expected: expected.to_string(),
+        offset: token.offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infer::infer_full;
+    use crate::test_util::*;
+    use crate::types::display_truncated;
+
+    #[test]
+    fn parses_lambda_with_if() {
+        assert_eq!(
+            parse("\\x -> if x then 1 else 2").unwrap(),
+            *func("x", if_(var("x"), int(1), int(2)))
+        );
+    }
+
+    #[test]
+    fn application_is_left_associative() {
+        assert_eq!(parse("f x (g y) 3").unwrap(), *call(call(call(var("f"), var("x")), call(var("g"), var("y"))), int(3)));
+    }
+
+    #[test]
+    fn parses_let_and_let_rec() {
+        assert_eq!(parse("let y = 3 in y").unwrap(), *let_("y", int(3), var("y")));
+        assert_eq!(
+            parse("let rec f = fun n -> f n in f").unwrap(),
+            Expression::ELetRec {
+                name: "f".to_string(),
+                value: func("n", call(var("f"), var("n"))),
+                body: var("f"),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_mutually_recursive_groups() {
+        assert_eq!(
+            parse("let rec f = fun n -> g n and g = fun n -> f n in f").unwrap(),
+            Expression::ELetRecGroup {
+                bindings: vec![
+                    ("f".to_string(), func("n", call(var("g"), var("n")))),
+                    ("g".to_string(), func("n", call(var("f"), var("n")))),
+                ],
+                body: var("f"),
+            }
+        );
+        // Only a let rec has a group
+        assert!(parse("let f = 1 and g = 2 in f").is_err());
+    }
+
+    #[test]
+    fn parses_let_patterns() {
+        let expected = let_pattern(
+            ptuple(vec![pvar("a"), pvar("b")]),
+            tuple(vec![int(1), bool(true)]),
+            if_(var("b"), var("a"), int(0)),
+        );
+        assert_eq!(parse("let (a, b) = (1, true) in if b then a else 0").unwrap(), *expected);
+        assert_eq!(parse_statement("let (a, b) = (1, 2) in a").unwrap(), Statement::Expr(*let_pattern(
+            ptuple(vec![pvar("a"), pvar("b")]),
+            tuple(vec![int(1), int(2)]),
+            var("a"),
+        )));
+        assert!(parse("let (a, b) = (1, 2)").is_err());
+    }
+
+    #[test]
+    fn parses_tuples_and_grouping() {
+        assert_eq!(parse("(1, true, ())").unwrap(), *tuple(vec![int(1), bool(true), tuple(vec![])]));
+        assert_eq!(parse("(f 1)").unwrap(), *call(var("f"), int(1)));
+    }
+
+    #[test]
+    fn parses_lists() {
+        assert_eq!(parse("[1, x, []]").unwrap(), *list(vec![int(1), var("x"), list(vec![])]));
+        assert_eq!(parse("head [1]").unwrap(), *call(var("head"), list(vec![int(1)])));
+    }
+
+    #[test]
+    fn parses_records_and_field_access() {
+        assert_eq!(
+            parse("f {a = 1, b = {}}.b.c").unwrap(),
+            *call(var("f"), field(field(record(vec![("a", int(1)), ("b", record(vec![]))]), "b"), "c"))
+        );
+    }
+
+    #[test]
+    fn parses_tuple_projections() {
+        let project = |tuple, index| Box::new(Expression::ETupleProject { tuple, index });
+        assert_eq!(parse("p.0.1").unwrap(), *project(project(var("p"), 0), 1));
+        assert_eq!(parse("r.x.0").unwrap(), *project(field(var("r"), "x"), 0));
+    }
+
+    #[test]
+    fn constructors_take_the_values_that_follow() {
+        assert_eq!(
+            parse("f (Just x Nothing) Nothing").unwrap(),
+            *call(
+                call(var("f"), constructor("Just", vec![var("x"), constructor("Nothing", vec![])])),
+                constructor("Nothing", vec![])
+            )
+        );
+    }
+
+    #[test]
+    fn parses_types() {
+        assert_eq!(
+            parse_type("(a -> Maybe b) -> List (a, Int) -> ()").unwrap(),
+            fun_type(
+                fun_type(tvar("a"), app_type("Maybe", vec![tvar("b")])),
+                fun_type(list_type(tuple_type(vec![tvar("a"), named("Int")])), tuple_type(vec![]))
+            )
+        );
+    }
+
+    #[test]
+    fn parses_data_declarations() {
+        assert_eq!(parse_statement("data Maybe a = Nothing | Just a").unwrap(), Statement::Data(maybe_data_type()));
+        let err = parse_statement("data maybe = Nothing").unwrap_err();
+        assert!(matches!(err, ParseError::UnexpectedToken { offset: 5, .. }));
+    }
+
+    #[test]
+    fn parses_matches() {
+        assert_eq!(
+            parse("match m with Just (x, 1) -> x | Nothing -> 0 | _ -> 2").unwrap(),
+            *match_(var("m"), vec![
+                (pcon("Just", vec![ptuple(vec![pvar("x"), pint(1)])]), var("x")),
+                (pcon("Nothing", vec![]), int(0)),
+                (pvar("_"), int(2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        // 1 + 2 * f 3 - 4 == x parses as ((1 + (2 * (f 3))) - 4) == x
+        let sum = binop(BinOp::Sub, binop(BinOp::Add, int(1), binop(BinOp::Mul, int(2), call(var("f"), int(3)))), int(4));
+        assert_eq!(parse("1 + 2 * f 3 - 4 == x").unwrap(), *binop(BinOp::Eq, sum, var("x")));
+        assert_eq!(
+            parse("\\n -> if n < 1 then 1 else n / 2").unwrap(),
+            *func("n", if_(binop(BinOp::Lt, var("n"), int(1)), int(1), binop(BinOp::Div, var("n"), int(2))))
+        );
+        assert!(parse("a < b < c").is_err());
+    }
+
+    #[test]
+    fn pipes_pass_the_value_on_to_the_function() {
+        // x |> f y |> g parses as g (f y x)
+        assert_eq!(parse("x |> f y |> g").unwrap(), *call(var("g"), call(call(var("f"), var("y")), var("x"))));
+        assert_eq!(parse("1 + 2 |> f").unwrap(), *call(var("f"), binop(BinOp::Add, int(1), int(2))));
+
+        let (type_, _subs) = infer_full(&parse("1 |> (fun x -> x + 1)").unwrap()).unwrap();
+        assert_eq!(type_, named("Int"));
+    }
+
+    #[test]
+    fn boolean_operators_bind_below_comparisons() {
+        // a || 1 < x && b parses as a || ((1 < x) && b)
+        let and = binop(BinOp::And, binop(BinOp::Lt, int(1), var("x")), var("b"));
+        assert_eq!(parse("a || 1 < x && b").unwrap(), *binop(BinOp::Or, var("a"), and));
+
+        let (type_, _subs) = infer_full(&parse("true && (1 < 2)").unwrap()).unwrap();
+        assert_eq!(type_, named("Bool"));
+        assert!(infer_full(&parse("1 && true").unwrap()).is_err());
+    }
+
+    #[test]
+    fn parses_annotations() {
+        assert_eq!(
+            parse("(\\x -> x) : Int -> Int").unwrap(),
+            *annot(func("x", var("x")), fun_type(named("Int"), named("Int")))
+        );
+        assert_eq!(parse("f (x : a) : List a").unwrap(), *annot(call(var("f"), annot(var("x"), tvar("a"))), list_type(tvar("a"))));
+    }
+
+    #[test]
+    fn lambdas_take_several_parameters() {
+        assert_eq!(parse("\\f x -> f x").unwrap(), *funcn(&["f", "x"], call(var("f"), var("x"))));
+        assert_eq!(parse("fun x -> x").unwrap(), *func("x", var("x")));
+    }
+
+    #[test]
+    fn lambda_body_extends_as_far_as_possible() {
+        assert_eq!(parse("\\f -> f 1").unwrap(), *func("f", call(var("f"), int(1))));
+    }
+
+    #[test]
+    fn reports_unexpected_tokens_and_end() {
+        assert_eq!(
+            parse("let = 3 in 3").unwrap_err(),
+            ParseError::UnexpectedToken { found: "=".to_string(), expected: "an identifier".to_string(), offset: 4 }
+        );
+        assert_eq!(
+            parse("if x then 1").unwrap_err(),
+            ParseError::UnexpectedEnd { expected: "'else'".to_string() }
+        );
+        assert_eq!(
+            parse("(x))").unwrap_err(),
+            ParseError::UnexpectedToken { found: ")".to_string(), expected: "end of input".to_string(), offset: 3 }
+        );
+    }
+
+    #[test]
+    fn let_without_in_is_a_binding_statement() {
+        assert_eq!(
+            parse_statement("let rec f = \\x -> f x").unwrap(),
+            Statement::Let { name: "f".to_string(), recursive: true, value: *func("x", call(var("f"), var("x"))) }
+        );
+        assert_eq!(parse_statement("let y = 3 in y").unwrap(), Statement::Expr(*let_("y", int(3), var("y"))));
+        assert_eq!(parse_statement("f 1").unwrap(), Statement::Expr(*call(var("f"), int(1))));
+    }
+
+    #[test]
+    fn keeps_comments_in_front_of_the_expression_that_follows() {
+        let src = "-- the answer\nlet x = -- one\n1 in x + 2";
+        let expr = parse_with_comments(src).unwrap();
+        let commented = |comment: &str, expr| {
+            Box::new(Expression::EComment { comments: vec![comment.to_string()], expr })
+        };
+        let body = binop(BinOp::Add, var("x"), int(2));
+        assert_eq!(expr, *commented("the answer", let_("x", commented("one", int(1)), body.clone())));
+        assert_eq!(expr.to_string(), src);
+
+        // The plain parser drops them
+        assert_eq!(parse(src).unwrap(), *let_("x", int(1), body));
+    }
+
+    #[test]
+    fn parsed_source_can_be_inferred() {
+        let expr = parse("let id = \\x -> x in if id true then id 1 else 2").unwrap();
+        let (type_, _) = infer_full(&expr).unwrap();
+        assert_eq!(display_truncated(&type_, usize::MAX), "Int");
+    }
+}
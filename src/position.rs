@@ -0,0 +1,61 @@
+//! Conversion of byte offsets in the source to positions
+
+/// The unit in which a column in the source is counted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnUnit {
+    // Bytes of the UTF-8 encoding
+    Utf8,
+    // Code units of the UTF-16 encoding, as used by LSP
+    Utf16,
+    // Unicode scalar values
+    Chars,
+}
+
+/// Convert a byte offset in the source to a zero based (line, column),
+/// with the column counted in UTF-16 code units like LSP expects
+pub fn offset_to_position(src: &str, offset: usize) -> (usize, usize) {
+    offset_to_position_in(src, offset, ColumnUnit::Utf16)
+}
+
+/// Convert a byte offset in the source to a zero based (line, column),
+/// with the column counted in the given unit. Offsets past the end are clamped
+pub fn offset_to_position_in(src: &str, offset: usize, unit: ColumnUnit) -> (usize, usize) {
+    let mut line = 0;
+    let mut column = 0;
+    for (idx, c) in src.char_indices() {
+        if idx >= offset {
+            break;
+        }
+        if c == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += match unit {
+                ColumnUnit::Utf8 => c.len_utf8(),
+                ColumnUnit::Utf16 => c.len_utf16(),
+                ColumnUnit::Chars => 1,
+            };
+        }
+    }
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_lines_and_columns() {
+        assert_eq!(offset_to_position("let x = 1\nin x", 13), (1, 3));
+    }
+
+    #[test]
+    fn counts_multi_byte_characters_in_the_chosen_unit() {
+        // 'é' is 2 bytes in UTF-8 and '𝔸' is 4 bytes in UTF-8 and 2 code units in UTF-16
+        let src = "é𝔸 x";
+        let offset = src.find('x').unwrap();
+        assert_eq!(offset_to_position_in(src, offset, ColumnUnit::Utf8), (0, 7));
+        assert_eq!(offset_to_position_in(src, offset, ColumnUnit::Utf16), (0, 4));
+        assert_eq!(offset_to_position_in(src, offset, ColumnUnit::Chars), (0, 3));
+    }
+}
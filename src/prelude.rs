@@ -0,0 +1,23 @@
+//! The commonly used items in one place, so a user of the crate can start with a single import
+//!
+//! ```
+//! use typing_test::prelude::*;
+//!
+//! let expr: Expression = parse("fun x -> x").unwrap();
+//! let (type_, _subs): (Box<Type>, Substitution) = infer_full(&expr).unwrap();
+//! assert!(matches!(*type_, Type::TFun { .. }));
+//!
+//! let mut ctx = Context::new(Env::intial());
+//! let _ = infer(&mut ctx, &Box::new(expr));
+//! let _: Result<Box<Type>, TypeError> = infer_str("1");
+//! let _: Result<Box<Type>, ParseError> = parse_type("a -> a");
+//! let _: Option<&TypeScheme> = ctx.env.0.get("not");
+//! ```
+
+pub use crate::ast::Expression;
+pub use crate::env::{Context, Env};
+pub use crate::error::{ParseError, TypeError};
+pub use crate::infer::{infer, infer_full, infer_str};
+pub use crate::parser::{parse, parse_type};
+pub use crate::types::{Type, TypeScheme};
+pub use crate::unify::Substitution;
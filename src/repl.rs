@@ -0,0 +1,276 @@
+//! An interactive session that infers one statement at a time and remembers the bindings
+
+use crate::ast::{redundant_ifs, shadowed_builtins, Expression, Warning};
+use crate::env::{generalize, Context, Env};
+use crate::error::{ParseError, TypeError};
+use crate::exhaustive::match_warnings;
+use crate::infer::infer;
+use crate::parser::{parse_statement, Statement};
+use crate::types::{display_truncated, free_type_vars, letter_name, rename_vars, Type};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Reasons why a line of input could not be handled
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplError {
+    // The line is not valid syntax
+    Parse(ParseError),
+    // The line parsed but does not type check
+    Type(TypeError),
+}
+
+impl fmt::Display for ReplError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplError::Parse(err) => write!(f, "Parse error: {}", err),
+            ReplError::Type(err) => write!(f, "Type error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReplError {}
+
+/// The state of a session, the context holds the bindings of all earlier lines
+pub struct Session {
+    pub ctx: Context,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { ctx: Context::new(Env::intial()) }
+    }
+
+    /// Handle a single line and return what should be printed for it.
+    /// A failing line leaves the bindings of the session untouched
+    pub fn eval(&mut self, line: &str) -> Result<String, ReplError> {
+        match parse_statement(line).map_err(ReplError::Parse)? {
+            Statement::Expr(expr) => {
+                // The analyses of the warnings expect an expression that type checks
+                let type_ = self.infer(&expr)?;
+                let warnings = self.warnings(&expr);
+                Ok(with_warnings(display_pretty(&type_, &self.ctx.var_aliases), warnings))
+            }
+            Statement::Let { name, recursive, value } => {
+                // Inferring `let name = value in name` gives the type of the binding,
+                // and lets the let rec rule take care of the recursion
+                let body = Box::new(Expression::EVar { name: name.clone() });
+                let expr = if recursive {
+                    Expression::ELetRec { name: name.clone(), value: Box::new(value), body }
+                } else {
+                    Expression::ELet { name: name.clone(), value: Box::new(value), body }
+                };
+                let type_ = self.infer(&expr)?;
+                let warnings = self.warnings(&expr);
+                let scheme = generalize(&self.ctx.env, &type_);
+                self.ctx.env.0.insert(name.clone(), scheme);
+                Ok(with_warnings(format!("{} : {}", name, display_pretty(&type_, &self.ctx.var_aliases)), warnings))
+            }
+            Statement::Data(data_type) => {
+                let lines: Vec<String> = data_type
+                    .constructors
+                    .iter()
+                    .map(|constructor| {
+                        let scheme = data_type.constructor_scheme(constructor);
+                        format!("{} : {}", constructor.name, display_pretty(&scheme.type_, &HashMap::new()))
+                    })
+                    .collect();
+                self.ctx.declare_data_type(data_type).map_err(ReplError::Type)?;
+                Ok(lines.join("\n"))
+            }
+        }
+    }
+
+    fn infer(&mut self, expr: &Expression) -> Result<Box<Type>, ReplError> {
+        let (type_, _subs) = infer(&mut self.ctx, &Box::new(expr.clone())).map_err(ReplError::Type)?;
+        Ok(type_)
+    }
+
+    /// Describe the suspicious parts of the expression. Only the names of the initial environment
+    /// count as builtins, bindings of earlier lines can be redefined without a warning
+    fn warnings(&self, expr: &Expression) -> Vec<String> {
+        let mut warnings = shadowed_builtins(expr, &Env::intial());
+        warnings.extend(redundant_ifs(expr));
+        warnings.extend(match_warnings(expr, &self.ctx.data_types));
+        warnings
+            .into_iter()
+            .map(|warning| match warning {
+                Warning::ShadowsBuiltin(name) => format!("Warning: {} shadows a builtin", name),
+                Warning::RedundantIf(_) => "Warning: both branches of an if are the same".to_string(),
+                Warning::NonExhaustiveMatch(missing) => format!("Warning: match does not cover {}", missing),
+                Warning::UnreachableArm(pattern) => format!("Warning: the arm for {} is never used", pattern),
+            })
+            .collect()
+    }
+}
+
+fn with_warnings(output: String, warnings: Vec<String>) -> String {
+    let mut lines = vec![output];
+    lines.extend(warnings);
+    lines.join("\n")
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+/// Display the type with its variables renamed to a, b, ... in order of appearance,
+/// except for the variables that have an alias. The letters skip the names of the aliases
+fn display_pretty(type_: &Box<Type>, aliases: &HashMap<String, String>) -> String {
+    let mut letters = (0..).map(letter_name).filter(|letter| !aliases.values().any(|alias| alias == letter));
+    let names: HashMap<String, String> = free_type_vars(type_)
+        .into_iter()
+        .map(|var| {
+            let name = aliases.get(&var).cloned().unwrap_or_else(|| letters.next().unwrap());
+            (var, name)
+        })
+        .collect();
+    let renamed = rename_vars(type_, &|name| names[name].clone());
+    display_truncated(&renamed, usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_the_type_of_an_expression() {
+        let mut session = Session::new();
+        assert_eq!(session.eval("\\x -> if x then 1 else 2").unwrap(), "Bool -> Int");
+        assert_eq!(session.eval("\\f -> \\x -> f x").unwrap(), "(a -> b) -> a -> b");
+    }
+
+    #[test]
+    fn aliases_name_the_type_variables() {
+        let aliases = [("T0".to_string(), "elem".to_string()), ("T9".to_string(), "a".to_string())];
+        let mut session = Session::new();
+        session.ctx = Context::new(Env::intial()).with_var_aliases(aliases.iter().cloned().collect());
+        // T0 is the type of x, the other variable skips the letter that an alias took
+        assert_eq!(session.eval("\\x -> \\f -> f x").unwrap(), "elem -> (elem -> b) -> b");
+    }
+
+    #[test]
+    fn bindings_persist_and_stay_polymorphic() {
+        let mut session = Session::new();
+        assert_eq!(session.eval("let id = \\x -> x").unwrap(), "id : a -> a");
+        assert_eq!(session.eval("id 1").unwrap(), "Int");
+        assert_eq!(session.eval("id true").unwrap(), "Bool");
+        assert_eq!(session.eval("let rec loop = \\x -> loop x").unwrap(), "loop : a -> b");
+    }
+
+    #[test]
+    fn data_declarations_make_constructors_available() {
+        let mut session = Session::new();
+        assert_eq!(
+            session.eval("data Maybe a = Nothing | Just a").unwrap(),
+            "Nothing : Maybe a\nJust : a -> Maybe a"
+        );
+        assert_eq!(session.eval("Just 1").unwrap(), "Maybe Int");
+        assert_eq!(session.eval("\\f -> Just (f Nothing)").unwrap(), "(Maybe a -> b) -> Maybe b");
+    }
+
+    #[test]
+    fn data_declarations_are_checked() {
+        let mut session = Session::new();
+        // Otherwise `match C v with | C x -> x` would turn a value of any type into any other type
+        assert_eq!(
+            session.eval("data Box = C b"),
+            Err(ReplError::Type(TypeError::UnboundTypeVariable { name: "b".to_string(), data_type: "Box".to_string() }))
+        );
+        assert!(session.eval("C 1").is_err());
+        assert_eq!(session.eval("data Box b = C b").unwrap(), "C : a -> Box a");
+
+        session.eval("data T = A Int | B").unwrap();
+        assert_eq!(
+            session.eval("data U = A"),
+            Err(ReplError::Type(TypeError::DuplicateConstructor { name: "A".to_string(), data_type: "T".to_string() }))
+        );
+        assert_eq!(session.eval("A 1").unwrap(), "T");
+        assert!(matches!(session.eval("data V = D | D"), Err(ReplError::Type(TypeError::DuplicateConstructor { .. }))));
+
+        // The same type can not be declared again, the earlier declaration stays
+        let duplicate = Err(ReplError::Type(TypeError::DuplicateDataType("T".to_string())));
+        assert_eq!(session.eval("data T = A Bool"), duplicate);
+        assert_eq!(session.eval("A 1").unwrap(), "T");
+        assert_eq!(session.eval("B").unwrap(), "T");
+    }
+
+    #[test]
+    fn data_types_can_not_be_declared_again() {
+        // Otherwise x would be matched with the constructors of the new T, and b would be an Int
+        let mut session = Session::new();
+        session.eval("data T = A Int | B Bool").unwrap();
+        session.eval("let x = A 1").unwrap();
+        let duplicate = Err(ReplError::Type(TypeError::DuplicateDataType("T".to_string())));
+        assert_eq!(session.eval("data T = B Bool"), duplicate);
+        assert_eq!(session.eval("match x with | B b -> b | A n -> n > 0").unwrap(), "Bool");
+    }
+
+    #[test]
+    fn built_in_types_can_not_be_declared() {
+        let mut session = Session::new();
+        let builtin = |name: &str| Err(ReplError::Type(TypeError::BuiltinTypeName(name.to_string())));
+        assert_eq!(session.eval("data Int = Z"), builtin("Int"));
+        assert!(session.eval("1 + Z").is_err());
+        assert_eq!(session.eval("data Bool = Yes"), builtin("Bool"));
+        assert!(session.eval("(\\b -> match b with | Yes -> 1) true").is_err());
+        assert_eq!(session.eval("data List a = Nil"), builtin("List"));
+        assert_eq!(session.eval("data String = S"), builtin("String"));
+        assert_eq!(session.eval("data Char = C"), builtin("Char"));
+    }
+
+    #[test]
+    fn type_parameters_are_distinct() {
+        let mut session = Session::new();
+        let duplicate = TypeError::DuplicateTypeParameter { name: "a".to_string(), data_type: "P".to_string() };
+        assert_eq!(session.eval("data P a a = C a"), Err(ReplError::Type(duplicate)));
+        assert!(session.eval("C 1").is_err());
+        assert_eq!(session.eval("data P a b = C a").unwrap(), "C : a -> P a b");
+    }
+
+    #[test]
+    fn warns_about_incomplete_matches() {
+        let mut session = Session::new();
+        session.eval("data Maybe a = Nothing | Just a").unwrap();
+        assert_eq!(
+            session.eval("\\m -> match m with | Just (Just x) -> x | Nothing -> 0").unwrap(),
+            "Maybe (Maybe Int) -> Int\nWarning: match does not cover Just Nothing"
+        );
+        assert_eq!(
+            session.eval("\\b -> match b with | x -> 1 | true -> 2").unwrap(),
+            "Bool -> Int\nWarning: the arm for true is never used"
+        );
+    }
+
+    #[test]
+    fn warns_about_redundant_ifs_and_shadowed_builtins() {
+        let mut session = Session::new();
+        assert_eq!(
+            session.eval("\\c -> if c then 1 else 1").unwrap(),
+            "Bool -> Int\nWarning: both branches of an if are the same"
+        );
+        assert_eq!(session.eval("let not = 1").unwrap(), "not : Int\nWarning: not shadows a builtin");
+        // Bindings of earlier lines are not builtins
+        session.eval("let id = \\x -> x").unwrap();
+        assert_eq!(session.eval("let id = 2").unwrap(), "id : Int");
+    }
+
+    #[test]
+    fn ill_typed_matches_are_errors_and_not_warnings() {
+        let mut session = Session::new();
+        session.eval("data Maybe a = Nothing | Just a").unwrap();
+        let err = session.eval("\\x -> match x with | (a, b) -> 1 | (c, d, e) -> 2").unwrap_err();
+        assert!(matches!(err, ReplError::Type(TypeError::Mismatch { .. })));
+        let err = session.eval("\\m -> match m with | Just -> 1 | Just x -> 2").unwrap_err();
+        assert!(matches!(err, ReplError::Type(TypeError::ArityMismatch { .. })));
+    }
+
+    #[test]
+    fn failing_lines_do_not_change_the_session() {
+        let mut session = Session::new();
+        assert!(matches!(session.eval("let x = y"), Err(ReplError::Type(TypeError::UnboundVariable(_)))));
+        assert!(matches!(session.eval("let x ="), Err(ReplError::Parse(_))));
+        assert!(!session.ctx.env.0.contains_key("x"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Short constructors for expressions and types, so tests read like the source they model
+
+use crate::ast::{BinOp, Expression, Pattern};
+use crate::error::TypeError;
+use crate::infer::infer_full;
+use crate::parser::{parse, parse_type};
+use crate::types::{alpha_equivalent, display_truncated, free_type_vars, Constructor, DataType, Type, TypeScheme};
+
+pub fn int(value: i32) -> Box<Expression> {
+    Box::new(Expression::EInt { value })
+}
+
+pub fn bool(value: bool) -> Box<Expression> {
+    Box::new(Expression::EBool { value })
+}
+
+pub fn var(name: &str) -> Box<Expression> {
+    Box::new(Expression::EVar { name: name.to_string() })
+}
+
+pub fn func(param: &str, body: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::EFunc { param: param.to_string(), body })
+}
+
+pub fn funcn(params: &[&str], body: Box<Expression>) -> Box<Expression> {
+    let params = params.iter().map(|param| param.to_string()).collect();
+    Box::new(Expression::EFuncN { params, body })
+}
+
+pub fn call(func: Box<Expression>, arg: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::ECall { func, arg })
+}
+
+pub fn tuple(elements: Vec<Box<Expression>>) -> Box<Expression> {
+    Box::new(Expression::ETuple { elements })
+}
+
+pub fn list(elements: Vec<Box<Expression>>) -> Box<Expression> {
+    Box::new(Expression::EList { elements })
+}
+
+pub fn record(fields: Vec<(&str, Box<Expression>)>) -> Box<Expression> {
+    let fields = fields.into_iter().map(|(field, value)| (field.to_string(), value)).collect();
+    Box::new(Expression::ERecord { fields })
+}
+
+pub fn field(record: Box<Expression>, field: &str) -> Box<Expression> {
+    Box::new(Expression::EFieldAccess { record, field: field.to_string() })
+}
+
+pub fn constructor(name: &str, args: Vec<Box<Expression>>) -> Box<Expression> {
+    Box::new(Expression::EConstructor { name: name.to_string(), args })
+}
+
+pub fn match_(scrutinee: Box<Expression>, arms: Vec<(Pattern, Box<Expression>)>) -> Box<Expression> {
+    Box::new(Expression::EMatch { scrutinee, arms })
+}
+
+pub fn pvar(name: &str) -> Pattern {
+    Pattern::PVar { name: name.to_string() }
+}
+
+pub fn pint(value: i32) -> Pattern {
+    Pattern::PInt { value }
+}
+
+pub fn pbool(value: bool) -> Pattern {
+    Pattern::PBool { value }
+}
+
+pub fn ptuple(elements: Vec<Pattern>) -> Pattern {
+    Pattern::PTuple { elements }
+}
+
+pub fn pcon(name: &str, args: Vec<Pattern>) -> Pattern {
+    Pattern::PConstructor { name: name.to_string(), args }
+}
+
+pub fn binop(op: BinOp, lhs: Box<Expression>, rhs: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::EBinOp { op, lhs, rhs })
+}
+
+pub fn annot(expr: Box<Expression>, type_: Box<Type>) -> Box<Expression> {
+    Box::new(Expression::EAnnot { expr, type_ })
+}
+
+pub fn if_(cond: Box<Expression>, true_b: Box<Expression>, false_b: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::EIf { cond, true_b, false_b })
+}
+
+pub fn let_(name: &str, value: Box<Expression>, body: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::ELet { name: name.to_string(), value, body })
+}
+
+pub fn let_pattern(pattern: Pattern, value: Box<Expression>, body: Box<Expression>) -> Box<Expression> {
+    Box::new(Expression::ELetPattern { pattern, value, body })
+}
+
+/// An extern value of the type in the source, quantified over all of its variables
+pub fn extern_(type_src: &str) -> Box<Expression> {
+    let type_ = parse_type(type_src).unwrap();
+    Box::new(Expression::EExtern { ty: TypeScheme { vars: free_type_vars(&type_), type_ } })
+}
+
+pub fn named(name: &str) -> Box<Type> {
+    Box::new(Type::TNamed { name: name.to_string() })
+}
+
+pub fn tvar(name: &str) -> Box<Type> {
+    Box::new(Type::TVar { name: name.to_string() })
+}
+
+pub fn fun_type(from: Box<Type>, to: Box<Type>) -> Box<Type> {
+    Box::new(Type::TFun { from, to })
+}
+
+pub fn tuple_type(elements: Vec<Box<Type>>) -> Box<Type> {
+    Box::new(Type::TTuple { elements })
+}
+
+pub fn list_type(element: Box<Type>) -> Box<Type> {
+    Type::list(element)
+}
+
+pub fn record_type(fields: Vec<(&str, Box<Type>)>) -> Box<Type> {
+    let fields = fields.into_iter().map(|(field, type_)| (field.to_string(), type_)).collect();
+    Box::new(Type::TRecord { fields, rest: None })
+}
+
+pub fn open_record_type(fields: Vec<(&str, Box<Type>)>, rest: &str) -> Box<Type> {
+    let fields = fields.into_iter().map(|(field, type_)| (field.to_string(), type_)).collect();
+    Box::new(Type::TRecord { fields, rest: Some(rest.to_string()) })
+}
+
+pub fn open_tuple_type(elements: Vec<(usize, Box<Type>)>, rest: &str) -> Box<Type> {
+    Box::new(Type::TOpenTuple { elements: elements.into_iter().collect(), rest: rest.to_string() })
+}
+
+pub fn app_type(name: &str, args: Vec<Box<Type>>) -> Box<Type> {
+    Box::new(Type::TApp { name: name.to_string(), args })
+}
+
+/// The declaration `data Maybe a = Nothing | Just a`
+pub fn maybe_data_type() -> DataType {
+    DataType {
+        name: "Maybe".to_string(),
+        params: vec!["a".to_string()],
+        constructors: vec![
+            Constructor { name: "Nothing".to_string(), fields: vec![] },
+            Constructor { name: "Just".to_string(), fields: vec![tvar("a")] },
+        ],
+    }
+}
+
+/// Assert that inference of the expression fails with an error the matcher accepts,
+/// eg. `|err| matches!(err, TypeError::OccursCheck { .. }) && err.to_string().contains("infinite")`
+pub fn assert_type_error(expr: &Expression, matcher: impl Fn(&TypeError) -> bool) {
+    match infer_full(expr) {
+        Ok((type_, _subs)) => panic!("expected an error, but inferred {}", display_truncated(&type_, usize::MAX)),
+        Err(err) => assert!(matcher(&err), "unexpected error: {}", err),
+    }
+}
+
+/// Assert that the source infers to the expected type in the initial environment,
+/// up to the names of the type variables, eg. `assert_infers("fun x -> x", "a -> a")`
+pub fn assert_infers(expr_src: &str, expected_type_src: &str) {
+    let (type_, _subs) = infer_full(&parse(expr_src).unwrap()).unwrap();
+    let expected = parse_type(expected_type_src).unwrap();
+    assert!(
+        alpha_equivalent(&type_, &expected),
+        "{} inferred {} but expected {}",
+        expr_src,
+        display_truncated(&type_, usize::MAX),
+        expected_type_src
+    );
+}
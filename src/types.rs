@@ -0,0 +1,553 @@
+//! The types that inference produces and helpers to inspect and print them
+
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Deref;
+
+/// This is the returned Type for the inference, so it is the outgoing type
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    // This is a named variable like bool
+    TNamed {
+        name: String,
+    },
+    // This is a stand in for when we do not know the type yet
+    TVar {
+        name: String,
+    },
+    // This is a function type that takes a type 'from' and returns a 'to'
+    TFun {
+        from: Box<Type>,
+        to: Box<Type>,
+    },
+    // This is a tuple type like (Int, Bool), the empty tuple is the unit type
+    TTuple {
+        elements: Vec<Box<Type>>,
+    },
+    // This is a type constructor applied to type arguments like List Int
+    TApp {
+        name: String,
+        args: Vec<Box<Type>>,
+    },
+    // This is a record type like { age: Int, name: Bool }, the fields are kept sorted by name.
+    // An open record { name: Bool | r } has the row variable r that stands for any other fields
+    TRecord {
+        fields: BTreeMap<String, Box<Type>>,
+        rest: Option<String>,
+    },
+    // This is a tuple of which only some elements are known, like the parameter of `fun p -> p.1`.
+    // The row variable stands for the whole tuple, it is bound once the size of the tuple is known
+    TOpenTuple {
+        elements: BTreeMap<usize, Box<Type>>,
+        rest: String,
+    },
+}
+
+/// A type that is quantified over some of its type variables like forall a. a -> a,
+/// every use of a binding with a scheme gets fresh variables for the quantified ones
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeScheme {
+    pub vars: Vec<String>,
+    pub type_: Box<Type>,
+}
+
+impl Type {
+    /// The type of lists with elements of the given type, List a
+    pub fn list(element: Box<Type>) -> Box<Type> {
+        Box::new(Type::TApp { name: "List".to_string(), args: vec![element] })
+    }
+}
+
+impl TypeScheme {
+    /// A scheme that does not quantify over anything, so it is just the type
+    pub fn mono(type_: Box<Type>) -> TypeScheme {
+        TypeScheme { vars: Vec::new(), type_ }
+    }
+}
+
+/// A declared sum type like `Maybe a = Nothing | Just a`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataType {
+    pub name: String,
+    pub params: Vec<String>,
+    pub constructors: Vec<Constructor>,
+}
+
+/// One of the alternatives of a sum type, together with the types of the values it holds
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constructor {
+    pub name: String,
+    pub fields: Vec<Box<Type>>,
+}
+
+impl DataType {
+    /// The type of the values of this data type, eg. Maybe a
+    pub fn applied_type(&self) -> Box<Type> {
+        if self.params.is_empty() {
+            return Box::new(Type::TNamed { name: self.name.clone() });
+        }
+        let args = self.params.iter().map(|param| Box::new(Type::TVar { name: param.clone() })).collect();
+        Box::new(Type::TApp { name: self.name.clone(), args })
+    }
+
+    /// The scheme of the constructor used as a curried function, eg. forall a. a -> Maybe a for Just
+    pub fn constructor_scheme(&self, constructor: &Constructor) -> TypeScheme {
+        let type_ = constructor
+            .fields
+            .iter()
+            .rev()
+            .fold(self.applied_type(), |to, from| Box::new(Type::TFun { from: from.clone(), to }));
+        TypeScheme { vars: self.params.clone(), type_ }
+    }
+}
+
+/// Collect the type variables in a type, in the order in which they first occur
+pub fn free_type_vars(t: &Box<Type>) -> Vec<String> {
+    fn go(t: &Type, found: &mut Vec<String>) {
+        match t {
+            Type::TNamed { .. } => {}
+            Type::TVar { name } => {
+                if !found.contains(name) {
+                    found.push(name.clone());
+                }
+            }
+            Type::TFun { from, to } => {
+                go(from, found);
+                go(to, found);
+            }
+            Type::TTuple { elements: types } | Type::TApp { args: types, .. } => {
+                for type_ in types {
+                    go(type_, found);
+                }
+            }
+            Type::TRecord { fields, rest } => {
+                for type_ in fields.values() {
+                    go(type_, found);
+                }
+                // The row variable is a type variable as well, so it is generalized like one
+                if let Some(name) = rest {
+                    if !found.contains(name) {
+                        found.push(name.clone());
+                    }
+                }
+            }
+            Type::TOpenTuple { elements, rest } => {
+                for type_ in elements.values() {
+                    go(type_, found);
+                }
+                if !found.contains(rest) {
+                    found.push(rest.clone());
+                }
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    go(t, &mut found);
+    found
+}
+
+/// Check if the type contains itself, recursively
+pub(crate) fn contains(t: &Box<Type>, name: &str) -> bool {
+    match t.deref() {
+        Type::TNamed { .. } => false,
+        Type::TVar { name: type_name } => name == type_name,
+        Type::TFun { from, to } => contains(from, name) || contains(to, name),
+        Type::TTuple { elements: types } | Type::TApp { args: types, .. } => {
+            types.iter().any(|type_| contains(type_, name))
+        }
+        Type::TRecord { fields, rest } => {
+            rest.as_deref() == Some(name) || fields.values().any(|type_| contains(type_, name))
+        }
+        Type::TOpenTuple { elements, rest } => rest == name || elements.values().any(|type_| contains(type_, name)),
+    }
+
+}
+
+/// Check if the type is fully known, so it does not contain any type variables
+pub fn is_ground(t: &Box<Type>) -> bool {
+    match t.deref() {
+        Type::TNamed { .. } => true,
+        Type::TVar { .. } => false,
+        Type::TFun { from, to } => is_ground(from) && is_ground(to),
+        Type::TTuple { elements: types } | Type::TApp { args: types, .. } => types.iter().all(is_ground),
+        Type::TRecord { fields, rest } => rest.is_none() && fields.values().all(is_ground),
+        Type::TOpenTuple { .. } => false,
+    }
+}
+
+/// Rename every type variable in the type using the given function
+/// eg. renaming with uppercase turns (a -> b) into (A -> B)
+pub fn rename_vars(ty: &Box<Type>, f: &impl Fn(&str) -> String) -> Box<Type> {
+    match ty.deref() {
+        Type::TNamed { .. } => ty.clone(),
+        Type::TVar { name } => Box::new(Type::TVar { name: f(name) }),
+        Type::TFun { from, to } => Box::new(Type::TFun { from: rename_vars(from, f), to: rename_vars(to, f) }),
+        Type::TTuple { elements } => Box::new(Type::TTuple {
+            elements: elements.iter().map(|element| rename_vars(element, f)).collect(),
+        }),
+        Type::TApp { name, args } => Box::new(Type::TApp {
+            name: name.clone(),
+            args: args.iter().map(|arg| rename_vars(arg, f)).collect(),
+        }),
+        Type::TRecord { fields, rest } => Box::new(Type::TRecord {
+            fields: fields.iter().map(|(field, type_)| (field.clone(), rename_vars(type_, f))).collect(),
+            rest: rest.as_ref().map(|name| f(name)),
+        }),
+        Type::TOpenTuple { elements, rest } => Box::new(Type::TOpenTuple {
+            elements: elements.iter().map(|(index, type_)| (*index, rename_vars(type_, f))).collect(),
+            rest: f(rest),
+        }),
+    }
+}
+
+/// Name for the n-th generated type variable: a, b, ..., z, a1, b1, ...
+pub(crate) fn letter_name(idx: usize) -> String {
+    let letter = (b'a' + (idx % 26) as u8) as char;
+    match idx / 26 {
+        0 => letter.to_string(),
+        round => format!("{}{}", letter, round),
+    }
+}
+
+/// Find the most specific type that both types are an instance of, by keeping their
+/// common structure and replacing the subtrees where they differ with type variables
+/// eg. anti-unifying (Int -> Bool) and (Int -> Int) gives (Int -> a)
+pub fn anti_unify(t1: &Box<Type>, t2: &Box<Type>) -> Box<Type> {
+    // The same pair of differing subtrees has to map to the same variable,
+    // and a new variable can not have the name of one that is already used
+    fn go(
+        t1: &Box<Type>,
+        t2: &Box<Type>,
+        seen: &mut Vec<(Box<Type>, Box<Type>, Box<Type>)>,
+        used: &mut Vec<String>,
+    ) -> Box<Type> {
+        match (t1.deref(), t2.deref()) {
+            (Type::TFun { from, to }, Type::TFun { from: from2, to: to2 }) => {
+                Box::new(Type::TFun { from: go(from, from2, seen, used), to: go(to, to2, seen, used) })
+            }
+            (Type::TTuple { elements }, Type::TTuple { elements: elements2 }) if elements.len() == elements2.len() => {
+                let elements = elements.iter().zip(elements2).map(|(l, r)| go(l, r, seen, used)).collect();
+                Box::new(Type::TTuple { elements })
+            }
+            (Type::TApp { name, args }, Type::TApp { name: name2, args: args2 })
+                if name == name2 && args.len() == args2.len() =>
+            {
+                let args = args.iter().zip(args2).map(|(l, r)| go(l, r, seen, used)).collect();
+                Box::new(Type::TApp { name: name.clone(), args })
+            }
+            (Type::TRecord { fields, rest }, Type::TRecord { fields: fields2, rest: rest2 })
+                if rest.is_none() && rest2.is_none() && fields.keys().eq(fields2.keys()) =>
+            {
+                let fields = fields
+                    .iter()
+                    .zip(fields2.values())
+                    .map(|((field, l), r)| (field.clone(), go(l, r, seen, used)))
+                    .collect();
+                Box::new(Type::TRecord { fields, rest: None })
+            }
+            _ if t1 == t2 => t1.clone(),
+            _ => {
+                if let Some((_, _, var)) = seen.iter().find(|(l, r, _)| l == t1 && r == t2) {
+                    return var.clone();
+                }
+                let name = (0..).map(letter_name).find(|name| !used.contains(name)).unwrap();
+                used.push(name.clone());
+                let var = Box::new(Type::TVar { name });
+                seen.push((t1.clone(), t2.clone(), var.clone()));
+                var
+            }
+        }
+    }
+
+    let mut used = free_type_vars(t1);
+    used.extend(free_type_vars(t2));
+    go(t1, t2, &mut Vec::new(), &mut used)
+}
+
+/// Render the type as (a -> Int) -> Bool, but only up to max_depth nested types,
+/// anything deeper is printed as `…` to keep very large types readable
+pub fn display_truncated(ty: &Box<Type>, max_depth: usize) -> String {
+    if max_depth == 0 {
+        return "…".to_string();
+    }
+    match ty.deref() {
+        Type::TNamed { name } | Type::TVar { name } => name.clone(),
+        Type::TFun { from, to } => {
+            let from_str = display_truncated(from, max_depth - 1);
+            let to_str = display_truncated(to, max_depth - 1);
+            // Arrows associate to the right, so only a function argument needs parentheses
+            match from.deref() {
+                Type::TFun { .. } if max_depth > 1 => format!("({}) -> {}", from_str, to_str),
+                _ => format!("{} -> {}", from_str, to_str),
+            }
+        }
+        Type::TTuple { elements } => {
+            let elements: Vec<String> = elements.iter().map(|element| display_truncated(element, max_depth - 1)).collect();
+            format!("({})", elements.join(", "))
+        }
+        Type::TApp { name, args } => {
+            let mut parts = vec![name.clone()];
+            for arg in args {
+                let arg_str = display_truncated(arg, max_depth - 1);
+                // Arguments that are themselves made of several parts need parentheses
+                match arg.deref() {
+                    Type::TFun { .. } if max_depth > 1 => parts.push(format!("({})", arg_str)),
+                    Type::TApp { args, .. } if !args.is_empty() && max_depth > 1 => parts.push(format!("({})", arg_str)),
+                    _ => parts.push(arg_str),
+                }
+            }
+            parts.join(" ")
+        }
+        Type::TRecord { fields, rest } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, type_)| format!("{}: {}", field, display_truncated(type_, max_depth - 1)))
+                .collect();
+            match rest {
+                Some(name) if fields.is_empty() => format!("{{ | {} }}", name),
+                Some(name) => format!("{{ {} | {} }}", fields.join(", "), name),
+                None => format!("{{ {} }}", fields.join(", ")),
+            }
+        }
+        Type::TOpenTuple { elements, rest } => {
+            // The elements that are not known before the last known one are left out as `_`
+            let last = elements.keys().next_back().map_or(0, |index| index + 1);
+            let mut parts: Vec<String> = (0..last)
+                .map(|index| match elements.get(&index) {
+                    Some(element) => display_truncated(element, max_depth - 1),
+                    None => "_".to_string(),
+                })
+                .collect();
+            parts.push(format!("..{}", rest));
+            format!("({})", parts.join(", "))
+        }
+    }
+}
+
+/// Count the number of leading arrows, which is the number of arguments the
+/// function takes before it gives a non function result, eg. 2 for (Int -> Int -> Bool)
+pub fn arrow_arity(ty: &Box<Type>) -> usize {
+    match ty.deref() {
+        Type::TFun { to, .. } => 1 + arrow_arity(to),
+        _ => 0,
+    }
+}
+
+/// Peel off all leading arrows and return what a fully applied call yields,
+/// eg. Bool for (Int -> Int -> Bool)
+pub fn result_type(ty: &Box<Type>) -> Box<Type> {
+    match ty.deref() {
+        Type::TFun { to, .. } => result_type(to),
+        _ => ty.clone(),
+    }
+}
+
+/// Return the argument types of a curried function in order,
+/// eg. [Int, Bool] for (Int -> Bool -> Float)
+pub fn param_types(ty: &Box<Type>) -> Vec<Box<Type>> {
+    match ty.deref() {
+        Type::TFun { from, to } => {
+            let mut params = vec![from.clone()];
+            params.extend(param_types(to));
+            params
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Collect the names of all named types the type refers to, eg. Int and Bool for (Int -> Bool)
+pub fn referenced_type_names(ty: &Box<Type>) -> HashSet<String> {
+    match ty.deref() {
+        Type::TNamed { name } => [name.clone()].iter().cloned().collect(),
+        Type::TVar { .. } => HashSet::new(),
+        Type::TFun { from, to } => {
+            let mut names = referenced_type_names(from);
+            names.extend(referenced_type_names(to));
+            names
+        }
+        Type::TTuple { elements } => elements.iter().flat_map(referenced_type_names).collect(),
+        Type::TApp { name, args } => {
+            let mut names: HashSet<String> = args.iter().flat_map(referenced_type_names).collect();
+            names.insert(name.clone());
+            names
+        }
+        Type::TRecord { fields, .. } => fields.values().flat_map(referenced_type_names).collect(),
+        Type::TOpenTuple { elements, .. } => elements.values().flat_map(referenced_type_names).collect(),
+    }
+}
+
+/// Check if two types are the same up to a consistent renaming of their type variables
+/// eg. (a -> b) and (c -> d) are, but (a -> a) and (c -> d) are not
+pub fn alpha_equivalent(t1: &Box<Type>, t2: &Box<Type>) -> bool {
+    // The renaming has to be one to one in both directions
+    fn same_var(name: &str, name2: &str, mapping: &mut Vec<(String, String)>) -> bool {
+        match mapping.iter().find(|(l, r)| l == name || r == name2) {
+            Some((l, r)) => l == name && r == name2,
+            None => {
+                mapping.push((name.to_string(), name2.to_string()));
+                true
+            }
+        }
+    }
+
+    fn go(t1: &Type, t2: &Type, mapping: &mut Vec<(String, String)>) -> bool {
+        match (t1, t2) {
+            (Type::TNamed { name }, Type::TNamed { name: name2 }) => name == name2,
+            (Type::TVar { name }, Type::TVar { name: name2 }) => same_var(name, name2, mapping),
+            (Type::TFun { from, to }, Type::TFun { from: from2, to: to2 }) => {
+                go(from, from2, mapping) && go(to, to2, mapping)
+            }
+            (Type::TTuple { elements }, Type::TTuple { elements: elements2 }) => {
+                elements.len() == elements2.len()
+                    && elements.iter().zip(elements2).all(|(l, r)| go(l, r, mapping))
+            }
+            (Type::TApp { name, args }, Type::TApp { name: name2, args: args2 }) => {
+                name == name2 && args.len() == args2.len() && args.iter().zip(args2).all(|(l, r)| go(l, r, mapping))
+            }
+            (Type::TRecord { fields, rest }, Type::TRecord { fields: fields2, rest: rest2 }) => {
+                let same_rest = match (rest, rest2) {
+                    (Some(name), Some(name2)) => same_var(name, name2, mapping),
+                    (None, None) => true,
+                    _ => false,
+                };
+                same_rest
+                    && fields.keys().eq(fields2.keys())
+                    && fields.values().zip(fields2.values()).all(|(l, r)| go(l, r, mapping))
+            }
+            (Type::TOpenTuple { elements, rest }, Type::TOpenTuple { elements: elements2, rest: rest2 }) => {
+                same_var(rest, rest2, mapping)
+                    && elements.keys().eq(elements2.keys())
+                    && elements.values().zip(elements2.values()).all(|(l, r)| go(l, r, mapping))
+            }
+            (_, _) => false,
+        }
+    }
+
+    go(t1, t2, &mut Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        app_type, fun_type, list_type, named, open_record_type, open_tuple_type, record_type, tuple_type, tvar,
+    };
+
+    #[test]
+    fn rename_vars_applies_the_function_to_every_variable() {
+        let ty = fun_type(tvar("a"), fun_type(named("Int"), tvar("b")));
+        let renamed = rename_vars(&ty, &|name| name.to_uppercase());
+        assert_eq!(renamed, fun_type(tvar("A"), fun_type(named("Int"), tvar("B"))));
+    }
+
+    #[test]
+    fn anti_unify_keeps_common_structure() {
+        let t1 = fun_type(named("Int"), named("Bool"));
+        let t2 = fun_type(named("Int"), named("Int"));
+        assert_eq!(anti_unify(&t1, &t2), fun_type(named("Int"), tvar("a")));
+    }
+
+    #[test]
+    fn anti_unify_reuses_variables_for_the_same_difference() {
+        let t1 = fun_type(named("Int"), named("Int"));
+        let t2 = fun_type(named("Bool"), named("Bool"));
+        assert_eq!(anti_unify(&t1, &t2), fun_type(tvar("a"), tvar("a")));
+    }
+
+    #[test]
+    fn anti_unify_picks_variables_that_are_not_in_the_types() {
+        let t1 = fun_type(tvar("a"), named("Int"));
+        let t2 = fun_type(tvar("a"), named("Bool"));
+        assert_eq!(anti_unify(&t1, &t2), fun_type(tvar("a"), tvar("b")));
+
+        let t1 = tuple_type(vec![tvar("b"), named("Int"), tvar("a")]);
+        let t2 = tuple_type(vec![tvar("c"), named("Bool"), tvar("a")]);
+        assert_eq!(anti_unify(&t1, &t2), tuple_type(vec![tvar("d"), tvar("e"), tvar("a")]));
+    }
+
+    #[test]
+    fn display_parenthesizes_function_arguments() {
+        let ty = fun_type(fun_type(tvar("a"), named("Int")), named("Bool"));
+        assert_eq!(display_truncated(&ty, usize::MAX), "(a -> Int) -> Bool");
+    }
+
+    #[test]
+    fn display_tuples() {
+        let ty = fun_type(tuple_type(vec![tvar("a"), named("Int")]), tuple_type(vec![]));
+        assert_eq!(display_truncated(&ty, usize::MAX), "(a, Int) -> ()");
+    }
+
+    #[test]
+    fn display_type_applications() {
+        let ty = list_type(fun_type(tvar("a"), list_type(named("Int"))));
+        assert_eq!(display_truncated(&ty, usize::MAX), "List (a -> List Int)");
+        assert_eq!(display_truncated(&list_type(list_type(named("Int"))), usize::MAX), "List (List Int)");
+        let option = app_type("Option", vec![named("Int")]);
+        assert_eq!(display_truncated(&list_type(option), usize::MAX), "List (Option Int)");
+    }
+
+    #[test]
+    fn display_records_with_sorted_fields() {
+        let ty = record_type(vec![("name", named("Bool")), ("age", tvar("a"))]);
+        assert_eq!(display_truncated(&ty, usize::MAX), "{ age: a, name: Bool }");
+    }
+
+    #[test]
+    fn open_records_have_their_row_variable_as_free_variable() {
+        let ty = open_record_type(vec![("name", tvar("a"))], "r");
+        assert_eq!(display_truncated(&ty, usize::MAX), "{ name: a | r }");
+        assert_eq!(free_type_vars(&ty), vec!["a", "r"]);
+        assert!(alpha_equivalent(&ty, &open_record_type(vec![("name", tvar("b"))], "s")));
+        assert!(!alpha_equivalent(&ty, &open_record_type(vec![("name", tvar("b"))], "b")));
+    }
+
+    #[test]
+    fn open_tuples_show_the_elements_they_know() {
+        let ty = open_tuple_type(vec![(2, tvar("a")), (0, named("Int"))], "r");
+        assert_eq!(display_truncated(&ty, usize::MAX), "(Int, _, a, ..r)");
+        assert_eq!(free_type_vars(&ty), vec!["a", "r"]);
+        assert!(alpha_equivalent(&ty, &open_tuple_type(vec![(0, named("Int")), (2, tvar("b"))], "s")));
+        assert!(!alpha_equivalent(&ty, &open_tuple_type(vec![(0, named("Int")), (1, tvar("b"))], "s")));
+    }
+
+    #[test]
+    fn display_truncates_deep_types() {
+        let ty = fun_type(named("Int"), fun_type(named("Int"), fun_type(named("Int"), named("Int"))));
+        assert_eq!(display_truncated(&ty, 3), "Int -> Int -> … -> …");
+    }
+
+    #[test]
+    fn decomposes_curried_function_types() {
+        let ty = fun_type(named("Int"), fun_type(named("Bool"), named("Float")));
+        assert_eq!(arrow_arity(&ty), 2);
+        assert_eq!(arrow_arity(&named("Int")), 0);
+        assert_eq!(result_type(&ty), named("Float"));
+        assert_eq!(param_types(&ty), vec![named("Int"), named("Bool")]);
+    }
+
+    #[test]
+    fn collects_referenced_type_names() {
+        let ty = fun_type(named("Int"), fun_type(tvar("a"), named("Bool")));
+        let expected: HashSet<String> = ["Int", "Bool"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(referenced_type_names(&ty), expected);
+
+        // The constructors of applied types are referenced too
+        let ty = app_type("List", vec![app_type("Option", vec![named("Int")])]);
+        let expected: HashSet<String> = ["List", "Option", "Int"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(referenced_type_names(&ty), expected);
+    }
+
+    #[test]
+    fn alpha_equivalence_requires_a_consistent_renaming() {
+        let ab = fun_type(tvar("a"), tvar("b"));
+        assert!(alpha_equivalent(&ab, &fun_type(tvar("c"), tvar("d"))));
+        assert!(!alpha_equivalent(&fun_type(tvar("a"), tvar("a")), &fun_type(tvar("c"), tvar("d"))));
+        assert!(!alpha_equivalent(&ab, &fun_type(tvar("c"), tvar("c"))));
+    }
+
+    #[test]
+    fn ground_types_have_no_variables() {
+        assert!(is_ground(&fun_type(named("Int"), named("Bool"))));
+        assert!(!is_ground(&fun_type(named("Int"), tvar("a"))));
+    }
+}
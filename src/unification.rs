@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 use crate::Expression::Var;
-use std::ops::Deref;
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 enum Expression {
     App(String, Vec<Expression>),
     Var(String),
@@ -16,9 +16,8 @@ type Substitution = HashMap<String, Expression>;
 /// should be applied recursively
 fn occurs_check(v: &Expression, term: &Expression, subst: &Substitution) -> bool {
     // Only check for variables
-    dbg!(term);
     if let Var(var_name) = v {
-        let result = match term.deref() {
+        let result = match term {
             Var(term_name) => {
                 // If the have the same name they are the same variables
                 if term_name == var_name {
@@ -32,7 +31,7 @@ fn occurs_check(v: &Expression, term: &Expression, subst: &Substitution) -> bool
                     false
                 }
             },
-            Expression::App(name, args) => {
+            Expression::App(_, args) => {
                 // Check for all parameters of the variable v occurs in the args
                 args.iter().any(|expr| occurs_check(v, expr, subst))
             },
@@ -0,0 +1,731 @@
+//! Substitutions and the unification of types
+
+use crate::error::TypeError;
+use crate::types::{contains, free_type_vars, Type, TypeScheme};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Deref;
+
+/// A map of type variables names to types assigned to them
+#[derive(Clone, Debug, Default)]
+pub struct Substitution(pub(crate) HashMap<String, Box<Type>>);
+
+impl Substitution {
+    pub fn new() -> Substitution {
+        Substitution(Default::default())
+    }
+
+    /// Look up the type that is assigned to a type variable
+    pub fn get(&self, name: &str) -> Option<&Box<Type>> {
+        self.0.get(name)
+    }
+}
+
+/// replace the type variables in a type that are
+/// present in the given substitution and return the
+/// type with those variables with their substituted values
+/// eg. Applying the substitution {"a": Bool, "b": Int}
+/// to a type (a -> b) will give type (Bool -> Int)
+pub fn appl_subs_to_type(subst: &Substitution, type_: &Box<Type>) -> Box<Type> {
+    match type_.deref() {
+        // In case of a name type like 'bool' just return it's type
+        Type::TNamed {name: _} => type_.clone(),
+        // In case of a type variable return it's type if it is in the substitution
+        // otherwise, just return the given type
+        Type::TVar {name} => {
+            subst.0.get(name).unwrap_or(type_).clone()
+        }
+        // For the function type arguments recursively apply for the subtypes
+        Type::TFun {from, to} => {
+            Box::new(Type::TFun {from: appl_subs_to_type(subst, from), to: appl_subs_to_type(subst, to)})
+        }
+        // The same for every element of a tuple and every argument of a type constructor
+        Type::TTuple {elements} => {
+            Box::new(Type::TTuple {elements: elements.iter().map(|element| appl_subs_to_type(subst, element)).collect()})
+        }
+        Type::TApp {name, args} => {
+            Box::new(Type::TApp {name: name.clone(), args: args.iter().map(|arg| appl_subs_to_type(subst, arg)).collect()})
+        }
+        // And for every field of a record, a substituted row variable adds its fields to the record
+        Type::TRecord {fields, rest} => {
+            let mut fields: BTreeMap<String, Box<Type>> =
+                fields.iter().map(|(field, type_)| (field.clone(), appl_subs_to_type(subst, type_))).collect();
+            let rest = match rest.as_ref().and_then(|name| subst.0.get(name).map(|row| (name, row))) {
+                Some((name, row)) => match appl_subs_to_type(subst, row).deref() {
+                    Type::TRecord {fields: row_fields, rest: row_rest} => {
+                        fields.extend(row_fields.iter().map(|(field, type_)| (field.clone(), type_.clone())));
+                        row_rest.clone()
+                    }
+                    Type::TVar {name: row_name} => Some(row_name.clone()),
+                    // Only records can be bound to a row variable, so leave anything else alone
+                    _ => Some(name.clone()),
+                },
+                None => rest.clone(),
+            };
+            Box::new(Type::TRecord {fields, rest})
+        }
+        // A substituted row variable of a tuple is the whole tuple, or a tuple that knows more of its elements
+        Type::TOpenTuple {elements, rest} => {
+            let mut elements: BTreeMap<usize, Box<Type>> =
+                elements.iter().map(|(index, type_)| (*index, appl_subs_to_type(subst, type_))).collect();
+            let rest = match subst.0.get(rest).map(|whole| appl_subs_to_type(subst, whole)) {
+                Some(whole) => match whole.deref() {
+                    Type::TTuple {..} => return whole,
+                    Type::TOpenTuple {elements: known, rest: whole_rest} => {
+                        elements.extend(known.iter().map(|(index, type_)| (*index, type_.clone())));
+                        whole_rest.clone()
+                    }
+                    Type::TVar {name} => name.clone(),
+                    // Only tuples can be bound to a row variable of a tuple, so leave anything else alone
+                    _ => rest.clone(),
+                },
+                None => rest.clone(),
+            };
+            Box::new(Type::TOpenTuple {elements, rest})
+        }
+    }
+}
+
+/// Apply the substitution to a scheme, leaving the variables it quantifies over alone
+pub fn appl_subs_to_scheme(subst: &Substitution, scheme: &TypeScheme) -> TypeScheme {
+    // Copying the substitution without the quantified variables is only needed when it binds one of them
+    if !scheme.vars.iter().any(|name| subst.0.contains_key(name)) {
+        return TypeScheme { vars: scheme.vars.clone(), type_: appl_subs_to_type(subst, &scheme.type_) };
+    }
+    let mut free_subst = Substitution::new();
+    for (name, type_) in subst.0.iter() {
+        if !scheme.vars.contains(name) {
+            free_subst.0.insert(name.clone(), type_.clone());
+        }
+    }
+    TypeScheme { vars: scheme.vars.clone(), type_: appl_subs_to_type(&free_subst, &scheme.type_) }
+}
+
+/// This function creates the substitution for a name and a type
+pub(crate) fn var_bind(name: &str, t: &Box<Type>) -> Result<Substitution, TypeError> {
+    // Return an empty substitution because it is the same type
+    if let Type::TVar {name: type_name} = t.deref() {
+        if name == type_name {
+            return Ok(Substitution::new())
+        }
+    }
+
+    // Check if the type contains a reference to itself
+    if contains(t, name) {
+        return Err(TypeError::OccursCheck { name: name.to_string(), type_: t.clone() });
+    }
+
+    // Create a new substitution that substitutes the name for the type
+    let mut sub = Substitution::new();
+    sub.0.insert(name.to_string(), t.clone());
+    Ok(sub)
+}
+
+/// Unify two types. Unifying two open records makes a new row variable for the fields they share,
+/// without a context its name can only be picked to be fresh in the two types
+pub fn unify(t1: &Box<Type>, t2: &Box<Type>) -> Result<Substitution, TypeError> {
+    // Only two open records need a name, so the used ones are only collected then
+    let mut used: Option<Vec<String>> = None;
+    let mut next = 0;
+    unify_with(t1, t2, &mut || {
+        let used = used.get_or_insert_with(|| {
+            let mut used = free_type_vars(t1);
+            used.extend(free_type_vars(t2));
+            used
+        });
+        loop {
+            let name = format!("r{}", next);
+            next += 1;
+            if !used.contains(&name) {
+                return name;
+            }
+        }
+    })
+}
+
+/// Unify two types, the new row variables are named by `fresh`,
+/// inference passes the generator of its context so they can not clash with any other variable
+pub fn unify_with(t1: &Box<Type>, t2: &Box<Type>, fresh: &mut dyn FnMut() -> String) -> Result<Substitution, TypeError> {
+    unify_types(t1, t2, fresh, None)
+}
+
+/// Unify two types like `unify_with`, but bind the type variables without the occurs check.
+/// The bindings are added to `bindings` instead, which is only sound when they are checked with
+/// `find_cycle` afterwards. The row variables of records and tuples are still checked right away,
+/// substituting a row follows the rows it is bound to and would never end on a cycle
+pub fn unify_deferred(
+    t1: &Box<Type>,
+    t2: &Box<Type>,
+    fresh: &mut dyn FnMut() -> String,
+    bindings: &mut Vec<(String, Box<Type>)>,
+) -> Result<Substitution, TypeError> {
+    unify_types(t1, t2, fresh, Some(bindings))
+}
+
+fn unify_types(
+    t1: &Box<Type>,
+    t2: &Box<Type>,
+    fresh: &mut dyn FnMut() -> String,
+    mut deferred: Option<&mut Vec<(String, Box<Type>)>>,
+) -> Result<Substitution, TypeError> {
+    match (t1.deref(), t2.deref()) {
+        (Type::TNamed {name}, Type::TNamed {name: name2}) if name == name2 => {
+            Ok(Substitution::new())
+        }
+        (Type::TVar {name}, _) => {
+            bind(name, t2, deferred)
+        }
+        (_, Type::TVar {name}) => {
+            bind(name, t1, deferred)
+        }
+        (Type::TFun {from, to}, Type::TFun {from: from2, to: to2}) => {
+            let s1 = unify_types(from, from2, fresh, deferred.as_deref_mut())?;
+            let s2 = unify_types(&appl_subs_to_type(&s1, to), &appl_subs_to_type(&s1, to2), fresh, deferred)?;
+            Ok(compose_substitution(&s2, &s1))
+        }
+        (Type::TTuple {elements}, Type::TTuple {elements: elements2}) if elements.len() == elements2.len() => {
+            unify_pairwise(elements, elements2, fresh, deferred)
+        }
+        (Type::TApp {name, args}, Type::TApp {name: name2, args: args2})
+            if name == name2 && args.len() == args2.len() => {
+            unify_pairwise(args, args2, fresh, deferred)
+        }
+        (Type::TRecord {fields, rest}, Type::TRecord {fields: fields2, rest: rest2}) => {
+            unify_records(t1, t2, (fields, rest), (fields2, rest2), fresh, deferred)
+        }
+        (Type::TOpenTuple {elements, rest}, Type::TTuple {elements: whole}) => {
+            unify_open_tuple(elements, rest, t2, whole, fresh, deferred)
+        }
+        (Type::TTuple {elements: whole}, Type::TOpenTuple {elements, rest}) => {
+            unify_open_tuple(elements, rest, t1, whole, fresh, deferred)
+        }
+        (Type::TOpenTuple {elements, rest}, Type::TOpenTuple {elements: elements2, rest: rest2}) => {
+            // Both are the same tuple, which then knows the elements of both
+            let mut known = elements.clone();
+            known.extend(elements2.iter().map(|(index, type_)| (*index, type_.clone())));
+            let both = Box::new(Type::TOpenTuple {elements: known, rest: fresh()});
+            let s_a = var_bind(rest, &both)?;
+            let s_b = if rest == rest2 {
+                Substitution::new()
+            } else {
+                var_bind(rest2, &appl_subs_to_type(&s_a, &both))?
+            };
+            let s1 = compose_substitution(&s_b, &s_a);
+
+            // The elements both know need to have the same types
+            let common: Vec<&usize> = elements.keys().filter(|index| elements2.contains_key(*index)).collect();
+            let types: Vec<Box<Type>> = common.iter().map(|index| appl_subs_to_type(&s1, &elements[index])).collect();
+            let types2: Vec<Box<Type>> = common.iter().map(|index| appl_subs_to_type(&s1, &elements2[index])).collect();
+            let s2 = unify_pairwise(&types, &types2, fresh, deferred)?;
+            Ok(compose_substitution(&s2, &s1))
+        }
+        (_, _) => Err(TypeError::Mismatch { expected: t1.clone(), found: t2.clone() })
+    }
+
+}
+
+/// Bind a type variable, without the occurs check when the bindings are collected to be checked later
+fn bind(name: &str, t: &Box<Type>, deferred: Option<&mut Vec<(String, Box<Type>)>>) -> Result<Substitution, TypeError> {
+    let bindings = match deferred {
+        Some(bindings) => bindings,
+        None => return var_bind(name, t),
+    };
+    if matches!(t.deref(), Type::TVar {name: type_name} if type_name == name) {
+        return Ok(Substitution::new());
+    }
+    bindings.push((name.to_string(), t.clone()));
+    let mut sub = Substitution::new();
+    sub.0.insert(name.to_string(), t.clone());
+    Ok(sub)
+}
+
+/// Look for a type variable that is bound to a type that contains it, directly or through the
+/// bindings of the variables in that type. Such a cycle is the infinite type that the occurs check
+/// would have reported at the binding that closed it
+pub fn find_cycle(bindings: &[(String, Box<Type>)]) -> Result<(), TypeError> {
+    // A variable that is bound more than once has all of its bindings as edges
+    fn visit<'a>(
+        name: &'a str,
+        bindings: &'a [(String, Box<Type>)],
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Result<(), TypeError> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        path.push(name);
+        for (bound, type_) in bindings.iter().filter(|(bound, _)| bound == name) {
+            for var in free_type_vars(type_) {
+                if path.contains(&var.as_str()) {
+                    return Err(TypeError::OccursCheck { name: bound.clone(), type_: type_.clone() });
+                }
+                if let Some((next, _)) = bindings.iter().find(|(next, _)| *next == var) {
+                    visit(next, bindings, path, done)?;
+                }
+            }
+        }
+        path.pop();
+        done.insert(name);
+        Ok(())
+    }
+
+    let mut done = HashSet::new();
+    for (name, _) in bindings {
+        visit(name, bindings, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
+}
+
+/// Unify two lists of types of the same length pair by pair,
+/// each pair with what the earlier pairs taught us
+fn unify_pairwise(
+    ts1: &[Box<Type>],
+    ts2: &[Box<Type>],
+    fresh: &mut dyn FnMut() -> String,
+    mut deferred: Option<&mut Vec<(String, Box<Type>)>>,
+) -> Result<Substitution, TypeError> {
+    // Composing after every pair applies the new bindings to all earlier ones, which is quadratic
+    // for wide tuples and records. So the bindings are only collected, and resolved once at the end
+    let mut found = Substitution::new();
+    for (t1, t2) in ts1.iter().zip(ts2) {
+        let s = unify_types(&resolve(&found, t1), &resolve(&found, t2), fresh, deferred.as_deref_mut())?;
+        found.0.extend(s.0);
+    }
+    let resolved = found.0.iter().map(|(name, type_)| (name.clone(), resolve(&found, type_))).collect();
+    Ok(Substitution(resolved))
+}
+
+/// Apply a substitution whose bindings can refer to each other until none of its variables are left
+fn resolve(subst: &Substitution, type_: &Box<Type>) -> Box<Type> {
+    // Every round takes a binding off the chains, so there are at most as many rounds as bindings.
+    // More could only be needed for a cycle, which unification without the occurs check leaves for later
+    let mut applied = appl_subs_to_type(subst, type_);
+    for _ in 0..subst.0.len() {
+        if !free_type_vars(&applied).iter().any(|name| subst.0.contains_key(name)) {
+            break;
+        }
+        applied = appl_subs_to_type(subst, &applied);
+    }
+    applied
+}
+
+/// Unify two records, the fields they share have to agree and the fields only one of them has
+/// have to be taken up by the row variable of the other one
+fn unify_records(
+    t1: &Box<Type>,
+    t2: &Box<Type>,
+    (fields, rest): (&BTreeMap<String, Box<Type>>, &Option<String>),
+    (fields2, rest2): (&BTreeMap<String, Box<Type>>, &Option<String>),
+    fresh: &mut dyn FnMut() -> String,
+    deferred: Option<&mut Vec<(String, Box<Type>)>>,
+) -> Result<Substitution, TypeError> {
+    let only_in = |fields: &BTreeMap<String, Box<Type>>, other: &BTreeMap<String, Box<Type>>| -> BTreeMap<String, Box<Type>> {
+        fields
+            .iter()
+            .filter(|(field, _)| !other.contains_key(*field))
+            .map(|(field, type_)| (field.clone(), type_.clone()))
+            .collect()
+    };
+    let only1 = only_in(fields, fields2);
+    let only2 = only_in(fields2, fields);
+
+    // A closed record can not take up any fields, so report the first one that it lacks
+    let missing = |only: &BTreeMap<String, Box<Type>>, record: &Box<Type>| match only.keys().next() {
+        Some(field) => Err(TypeError::MissingField { field: field.clone(), record: record.clone() }),
+        None => Ok(()),
+    };
+    let row = |fields: BTreeMap<String, Box<Type>>, rest: Option<String>| Box::new(Type::TRecord { fields, rest });
+
+    let s1 = match (rest, rest2) {
+        (None, None) => {
+            missing(&only1, t2)?;
+            missing(&only2, t1)?;
+            Substitution::new()
+        }
+        (Some(name), None) => {
+            missing(&only1, t2)?;
+            var_bind(name, &row(only2, None))?
+        }
+        (None, Some(name2)) => {
+            missing(&only2, t1)?;
+            var_bind(name2, &row(only1, None))?
+        }
+        (Some(name), Some(name2)) if name == name2 => {
+            // The same row can not have different extra fields
+            missing(&only1, t2)?;
+            missing(&only2, t1)?;
+            Substitution::new()
+        }
+        (Some(name), Some(name2)) => {
+            // Both rows are extended with what the other one has, and share the rest
+            let shared = Some(fresh());
+            let s_a = var_bind(name, &row(only2, shared.clone()))?;
+            let s_b = var_bind(name2, &appl_subs_to_type(&s_a, &row(only1, shared)))?;
+            compose_substitution(&s_b, &s_a)
+        }
+    };
+
+    // The fields both records have need to have the same types
+    let common: Vec<&String> = fields.keys().filter(|field| fields2.contains_key(*field)).collect();
+    let types: Vec<Box<Type>> = common.iter().map(|field| appl_subs_to_type(&s1, &fields[*field])).collect();
+    let types2: Vec<Box<Type>> = common.iter().map(|field| appl_subs_to_type(&s1, &fields2[*field])).collect();
+    let s2 = unify_pairwise(&types, &types2, fresh, deferred)?;
+    Ok(compose_substitution(&s2, &s1))
+}
+
+/// Unify a tuple that is only known to have some elements with a whole tuple,
+/// the whole tuple needs to have those elements and becomes what the row variable stands for
+fn unify_open_tuple(
+    elements: &BTreeMap<usize, Box<Type>>,
+    rest: &str,
+    tuple: &Box<Type>,
+    whole: &[Box<Type>],
+    fresh: &mut dyn FnMut() -> String,
+    deferred: Option<&mut Vec<(String, Box<Type>)>>,
+) -> Result<Substitution, TypeError> {
+    if let Some(index) = elements.keys().find(|index| **index >= whole.len()) {
+        return Err(TypeError::TupleIndexOutOfRange { index: *index, tuple: tuple.clone() });
+    }
+    let s1 = var_bind(rest, tuple)?;
+    let types: Vec<Box<Type>> = elements.values().map(|type_| appl_subs_to_type(&s1, type_)).collect();
+    let types2: Vec<Box<Type>> = elements.keys().map(|index| appl_subs_to_type(&s1, &whole[*index])).collect();
+    let s2 = unify_pairwise(&types, &types2, fresh, deferred)?;
+    Ok(compose_substitution(&s2, &s1))
+}
+
+/// Combines two subsitutios, so that applying the result is the same as
+/// first applying s2 and then s1
+pub fn compose_substitution(s1: &Substitution, s2: &Substitution) -> Substitution {
+    // Keep the bindings of s1, otherwise they are lost for variables s2 does not know about
+    let mut subs = Substitution(s1.0.clone());
+    for (name, type_) in s2.0.iter() {
+        subs.0.insert(name.clone(), appl_subs_to_type(s1, type_));
+    };
+    subs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        fun_type, list_type, named, open_record_type, open_tuple_type, record_type, tuple_type, tvar,
+    };
+    use crate::types::alpha_equivalent;
+
+    #[test]
+    fn unify_binds_variables() {
+        let subs = unify(&tvar("a"), &named("Int")).unwrap();
+        assert_eq!(subs.get("a"), Some(&named("Int")));
+    }
+
+    #[test]
+    fn unify_resolves_bindings_through_other_variables() {
+        let subs = unify(&fun_type(tvar("a"), tvar("a")), &fun_type(tvar("b"), named("Int"))).unwrap();
+        assert_eq!(appl_subs_to_type(&subs, &tvar("a")), named("Int"));
+        assert_eq!(appl_subs_to_type(&subs, &tvar("b")), named("Int"));
+    }
+
+    #[test]
+    fn unify_reports_mismatches() {
+        let err = unify(&named("Int"), &named("Bool")).unwrap_err();
+        assert_eq!(err, TypeError::Mismatch { expected: named("Int"), found: named("Bool") });
+    }
+
+    #[test]
+    fn unify_rejects_infinite_types() {
+        let err = unify(&tvar("a"), &fun_type(tvar("a"), named("Int"))).unwrap_err();
+        assert!(matches!(err, TypeError::OccursCheck { .. }));
+    }
+
+    #[test]
+    fn unify_tuples_element_wise() {
+        let t1 = tuple_type(vec![tvar("a"), tvar("a")]);
+        let t2 = tuple_type(vec![named("Int"), tvar("b")]);
+        let subs = unify(&t1, &t2).unwrap();
+        assert_eq!(appl_subs_to_type(&subs, &t2), tuple_type(vec![named("Int"), named("Int")]));
+
+        let err = unify(&tuple_type(vec![named("Int")]), &tuple_type(vec![named("Int"), named("Int")])).unwrap_err();
+        assert!(matches!(err, TypeError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn unify_tuples_resolves_bindings_of_earlier_elements() {
+        // a := b, then b := c, then c := Int, every variable ends up as Int
+        let t1 = tuple_type(vec![tvar("a"), tvar("b"), tvar("c")]);
+        let t2 = tuple_type(vec![tvar("b"), tvar("c"), named("Int")]);
+        let subs = unify(&t1, &t2).unwrap();
+        for name in ["a", "b", "c"].iter() {
+            assert_eq!(subs.get(name), Some(&named("Int")));
+        }
+    }
+
+    #[test]
+    fn unify_type_applications_by_name_and_arguments() {
+        let subs = unify(&list_type(tvar("a")), &list_type(named("Int"))).unwrap();
+        assert_eq!(subs.get("a"), Some(&named("Int")));
+
+        let maybe_int = Box::new(Type::TApp { name: "Maybe".to_string(), args: vec![named("Int")] });
+        assert!(unify(&list_type(named("Int")), &maybe_int).is_err());
+    }
+
+    #[test]
+    fn unify_records_field_by_field() {
+        let r1 = record_type(vec![("age", tvar("a")), ("name", named("Bool"))]);
+        let r2 = record_type(vec![("name", tvar("b")), ("age", named("Int"))]);
+        let subs = unify(&r1, &r2).unwrap();
+        assert_eq!(subs.get("a"), Some(&named("Int")));
+        assert_eq!(subs.get("b"), Some(&named("Bool")));
+
+        let r3 = record_type(vec![("age", named("Int"))]);
+        assert_eq!(unify(&r3, &r2).unwrap_err(), TypeError::MissingField { field: "name".to_string(), record: r3 });
+    }
+
+    #[test]
+    fn open_record_takes_up_the_extra_fields() {
+        let open = open_record_type(vec![("name", tvar("a"))], "r");
+        let closed = record_type(vec![("name", named("Bool")), ("age", named("Int"))]);
+        let subs = unify(&open, &closed).unwrap();
+        assert_eq!(appl_subs_to_type(&subs, &open), closed);
+
+        let err = unify(&open_record_type(vec![("email", tvar("a"))], "r"), &closed).unwrap_err();
+        assert_eq!(err, TypeError::MissingField { field: "email".to_string(), record: closed });
+    }
+
+    #[test]
+    fn open_tuples_become_the_whole_tuple() {
+        let open = open_tuple_type(vec![(1, tvar("a"))], "r");
+        let whole = tuple_type(vec![named("Int"), named("Bool")]);
+        let subs = unify(&open, &whole).unwrap();
+        assert_eq!(subs.get("a"), Some(&named("Bool")));
+        assert_eq!(appl_subs_to_type(&subs, &open), whole);
+
+        let err = unify(&open_tuple_type(vec![(2, tvar("a"))], "r"), &whole).unwrap_err();
+        assert_eq!(err, TypeError::TupleIndexOutOfRange { index: 2, tuple: whole });
+    }
+
+    #[test]
+    fn two_open_tuples_know_the_elements_of_both() {
+        let t1 = open_tuple_type(vec![(0, named("Int")), (1, tvar("a"))], "r");
+        let t2 = open_tuple_type(vec![(1, named("Bool")), (3, tvar("b"))], "s");
+        let subs = unify(&t1, &t2).unwrap();
+        let both = appl_subs_to_type(&subs, &t1);
+        assert_eq!(both, appl_subs_to_type(&subs, &t2));
+        let expected = open_tuple_type(vec![(0, named("Int")), (1, named("Bool")), (3, tvar("b"))], "q");
+        assert!(alpha_equivalent(&both, &expected));
+    }
+
+    #[test]
+    fn two_open_records_share_a_row() {
+        let t1 = open_record_type(vec![("name", named("Bool"))], "r");
+        let t2 = open_record_type(vec![("age", named("Int"))], "s");
+        let subs = unify(&t1, &t2).unwrap();
+        let both = appl_subs_to_type(&subs, &t1);
+        assert_eq!(both, appl_subs_to_type(&subs, &t2));
+        assert!(matches!(both.deref(), Type::TRecord { fields, rest: Some(_) } if fields.len() == 2));
+    }
+
+    #[test]
+    fn shared_rows_get_fresh_names() {
+        // Gluing the row names together, r and 0_s, would give the r_0_s that is already used
+        let t1 = open_record_type(vec![("name", open_record_type(vec![], "r_0_s"))], "r");
+        let t2 = open_record_type(vec![("age", named("Int"))], "0_s");
+        let subs = unify(&t1, &t2).unwrap();
+        let both = appl_subs_to_type(&subs, &t1);
+        assert!(matches!(both.deref(), Type::TRecord { rest: Some(rest), .. } if rest != "r_0_s" && rest != "r"));
+
+        // With a generator the names come from it
+        let mut names = vec!["T7".to_string()];
+        let subs = unify_with(&t1, &t2, &mut || names.pop().unwrap()).unwrap();
+        assert!(matches!(appl_subs_to_type(&subs, &t1).deref(), Type::TRecord { rest: Some(rest), .. } if rest == "T7"));
+    }
+
+    #[test]
+    fn occurs_check_looks_at_row_variables() {
+        let t1 = open_record_type(vec![], "r");
+        let t2 = open_record_type(vec![("self", open_record_type(vec![], "r"))], "s");
+        assert!(matches!(unify(&t1, &t2).unwrap_err(), TypeError::OccursCheck { .. }));
+    }
+
+    #[test]
+    fn occurs_check_looks_inside_tuples() {
+        let err = unify(&tvar("a"), &tuple_type(vec![named("Int"), tvar("a")])).unwrap_err();
+        assert!(matches!(err, TypeError::OccursCheck { .. }));
+    }
+
+    #[test]
+    fn deferred_bindings_are_scanned_for_cycles() {
+        let mut bindings = Vec::new();
+        let infinite = fun_type(tvar("a"), named("Int"));
+        assert!(unify_deferred(&tvar("a"), &infinite, &mut || unreachable!(), &mut bindings).is_ok());
+        assert!(matches!(find_cycle(&bindings).unwrap_err(), TypeError::OccursCheck { name, .. } if name == "a"));
+
+        // A cycle through other variables is an infinite type as well
+        let bindings = vec![
+            ("a".to_string(), fun_type(tvar("b"), named("Int"))),
+            ("b".to_string(), list_type(tvar("c"))),
+            ("c".to_string(), tvar("a")),
+        ];
+        assert!(find_cycle(&bindings).is_err());
+        assert!(find_cycle(&bindings[..2]).is_ok());
+    }
+
+    #[test]
+    fn composition_applies_the_right_substitution_first() {
+        let mut s1 = Substitution::new();
+        s1.0.insert("b".to_string(), named("Int"));
+        let mut s2 = Substitution::new();
+        s2.0.insert("a".to_string(), tvar("b"));
+
+        let ty = fun_type(tvar("a"), tvar("b"));
+        let composed = compose_substitution(&s1, &s2);
+        assert_eq!(appl_subs_to_type(&composed, &ty), appl_subs_to_type(&s1, &appl_subs_to_type(&s2, &ty)));
+        assert_eq!(appl_subs_to_type(&composed, &ty), fun_type(named("Int"), named("Int")));
+    }
+
+    /// A small seeded generator, enough to drive the property test without a proptest dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % n as u64) as usize
+        }
+    }
+
+    const POOL: [&str; 3] = ["a", "b", "c"];
+
+    fn random_type(rng: &mut Rng, depth: usize) -> Box<Type> {
+        match rng.below(if depth == 0 { 2 } else { 3 }) {
+            0 => tvar(POOL[rng.below(POOL.len())]),
+            1 => named(["Int", "Bool"][rng.below(2)]),
+            _ => fun_type(random_type(rng, depth - 1), random_type(rng, depth - 1)),
+        }
+    }
+
+    fn random_substitution(rng: &mut Rng) -> Substitution {
+        let mut subs = Substitution::new();
+        for name in POOL.iter() {
+            if rng.below(2) == 0 {
+                subs.0.insert(name.to_string(), random_type(rng, 2));
+            }
+        }
+        subs
+    }
+
+    #[test]
+    fn composition_is_associative() {
+        let mut rng = Rng(1);
+        for _ in 0..500 {
+            let s1 = random_substitution(&mut rng);
+            let s2 = random_substitution(&mut rng);
+            let s3 = random_substitution(&mut rng);
+            let left = compose_substitution(&compose_substitution(&s1, &s2), &s3);
+            let right = compose_substitution(&s1, &compose_substitution(&s2, &s3));
+            let ty = random_type(&mut rng, 3);
+            assert_eq!(appl_subs_to_type(&left, &ty), appl_subs_to_type(&right, &ty));
+        }
+    }
+
+    /// Like `random_type`, but with tuples and lists as well, so `unify_pairwise` gets its share
+    fn random_compound_type(rng: &mut Rng, depth: usize) -> Box<Type> {
+        match rng.below(if depth == 0 { 2 } else { 5 }) {
+            0 => tvar(POOL[rng.below(POOL.len())]),
+            1 => named(["Int", "Bool"][rng.below(2)]),
+            2 => fun_type(random_compound_type(rng, depth - 1), random_compound_type(rng, depth - 1)),
+            3 => tuple_type((0..2 + rng.below(2)).map(|_| random_compound_type(rng, depth - 1)).collect()),
+            _ => list_type(random_compound_type(rng, depth - 1)),
+        }
+    }
+
+    /// The textbook unifier to check `unify` against: it takes the equations one at a time
+    /// and applies every binding to the equations left and to the earlier bindings straight away
+    fn naive_unify(t1: &Box<Type>, t2: &Box<Type>) -> Option<Substitution> {
+        let mut equations = vec![(t1.clone(), t2.clone())];
+        let mut subs = Substitution::new();
+        while let Some((left, right)) = equations.pop() {
+            match (left.deref(), right.deref()) {
+                (Type::TVar {name}, Type::TVar {name: name2}) if name == name2 => {}
+                (Type::TVar {name}, _) | (_, Type::TVar {name}) => {
+                    let other = if left == tvar(name) { &right } else { &left };
+                    if contains(other, name) {
+                        return None;
+                    }
+                    let mut bind = Substitution::new();
+                    bind.0.insert(name.clone(), other.clone());
+                    for (l, r) in equations.iter_mut() {
+                        *l = appl_subs_to_type(&bind, l);
+                        *r = appl_subs_to_type(&bind, r);
+                    }
+                    for type_ in subs.0.values_mut() {
+                        *type_ = appl_subs_to_type(&bind, type_);
+                    }
+                    subs.0.insert(name.clone(), other.clone());
+                }
+                (Type::TNamed {name}, Type::TNamed {name: name2}) if name == name2 => {}
+                (Type::TFun {from, to}, Type::TFun {from: from2, to: to2}) => {
+                    equations.push((from.clone(), from2.clone()));
+                    equations.push((to.clone(), to2.clone()));
+                }
+                (Type::TTuple {elements}, Type::TTuple {elements: elements2}) if elements.len() == elements2.len() => {
+                    equations.extend(elements.iter().cloned().zip(elements2.iter().cloned()));
+                }
+                (Type::TApp {name, args}, Type::TApp {name: name2, args: args2})
+                    if name == name2 && args.len() == args2.len() => {
+                    equations.extend(args.iter().cloned().zip(args2.iter().cloned()));
+                }
+                _ => return None,
+            }
+        }
+        Some(subs)
+    }
+
+    #[test]
+    fn unify_agrees_with_a_naive_unifier() {
+        let mut rng = Rng(7);
+        let mut unified = 0;
+        for _ in 0..2000 {
+            let t1 = random_compound_type(&mut rng, 3);
+            // Half of the pairs are an instance of each other, so plenty of them unify
+            let t2 = if rng.below(2) == 0 {
+                random_compound_type(&mut rng, 3)
+            } else {
+                let mut subs = Substitution::new();
+                for name in POOL.iter() {
+                    if rng.below(2) == 0 {
+                        subs.0.insert(name.to_string(), random_compound_type(&mut rng, 1));
+                    }
+                }
+                appl_subs_to_type(&subs, &t1)
+            };
+
+            match (unify(&t1, &t2), naive_unify(&t1, &t2)) {
+                (Ok(subs), Some(expected)) => {
+                    unified += 1;
+                    assert_eq!(appl_subs_to_type(&subs, &t1), appl_subs_to_type(&subs, &t2), "{:?} ~ {:?}", t1, t2);
+                    // Most general unifiers are the same up to renaming, so what they make of every variable
+                    // and of the types has to be alpha equivalent
+                    let mut all = POOL.iter().map(|name| tvar(name)).collect::<Vec<_>>();
+                    all.push(t1.clone());
+                    let all = tuple_type(all);
+                    assert!(
+                        alpha_equivalent(&appl_subs_to_type(&subs, &all), &appl_subs_to_type(&expected, &all)),
+                        "{:?} ~ {:?}", t1, t2
+                    );
+                }
+                (Err(_), None) => {}
+                (found, expected) => {
+                    panic!("{:?} ~ {:?}: unify gave {:?}, the naive unifier {:?}", t1, t2, found, expected)
+                }
+            }
+        }
+        assert!(unified > 500, "only {} pairs unified", unified);
+    }
+
+    #[test]
+    fn substitution_skips_quantified_variables() {
+        let scheme = TypeScheme { vars: vec!["a".to_string()], type_: fun_type(tvar("a"), tvar("b")) };
+        let mut subs = Substitution::new();
+        subs.0.insert("a".to_string(), named("Int"));
+        subs.0.insert("b".to_string(), named("Bool"));
+        assert_eq!(appl_subs_to_scheme(&subs, &scheme).type_, fun_type(tvar("a"), named("Bool")));
+    }
+}